@@ -0,0 +1,290 @@
+//! The `#[stubbable]` attribute, re-exported as `rust_stub::stubbable`.
+//!
+//! The attribute reads a trait definition and hands `create_stub!` the method list a test would
+//! otherwise write by hand, so the stub never drifts from the trait it stands in for.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{FnArg, GenericArgument, Ident, ItemTrait, Lifetime, Pat, PathArguments, Receiver, ReturnType,
+          TraitItem, TraitItemFn, Type, TypeImplTrait, TypeReference};
+use syn::visit::Visit;
+
+const STUB_KINDS: &[&str] = &["ArgWatchingStub", "InterceptingStub", "SimpleStub", "Stub", "nostub"];
+
+/// Generates a `<Trait>Stub` struct with one stub field per method, plus the trait impl for it.
+///
+/// Methods whose args are all known to be `Clone + PartialEq` (primitives, `String`, and std
+/// containers and tuples of them, or shared references to those) get an `ArgWatchingStub`, which
+/// records borrowed args in owned form; the rest get an `InterceptingStub`.
+/// `#[stub(SimpleStub)]`, `#[stub(ArgWatchingStub)]`, `#[stub(InterceptingStub)]`, `#[stub(Stub)]`
+/// or `#[stub(nostub)]` on a method overrides the choice. Methods with a default body keep it unless
+/// they carry one of those attributes.
+///
+/// The stub struct takes the trait's visibility, and `#[stubbable(MyStub)]` renames it. There is no
+/// delegating form: `#[stub(nostub)]` methods panic, so partial stubs need `create_stub!`.
+#[proc_macro_attribute]
+pub fn stubbable(attr: TokenStream, item: TokenStream) -> TokenStream {
+  let mut item_trait = parse_macro_input!(item as ItemTrait);
+  let stub_ident = if attr.is_empty() {
+    format_ident!("{}Stub", item_trait.ident)
+  } else {
+    parse_macro_input!(attr as Ident)
+  };
+
+  let expanded = match stub_entries(&mut item_trait) {
+    Ok(entries) => {
+      let vis = &item_trait.vis;
+      let trait_ident = &item_trait.ident;
+      quote! {
+        #item_trait
+
+        ::rust_stub::create_stub! {
+          #vis #stub_ident as #trait_ident {
+            #(#entries)*
+          }
+        }
+      }
+    },
+    Err(err) => {
+      let err = err.to_compile_error();
+      quote! {
+        #item_trait
+        #err
+      }
+    },
+  };
+  expanded.into()
+}
+
+fn stub_entries(item_trait: &mut ItemTrait) -> syn::Result<Vec<TokenStream2>> {
+  if !item_trait.generics.params.is_empty() || item_trait.generics.where_clause.is_some() {
+    return Err(syn::Error::new_spanned(&item_trait.generics, "#[stubbable] can't stub generic traits"));
+  }
+  if item_trait.unsafety.is_some() {
+    return Err(syn::Error::new_spanned(item_trait.unsafety, "#[stubbable] can't stub unsafe traits"));
+  }
+
+  let mut entries = Vec::new();
+  for item in item_trait.items.iter_mut() {
+    match *item {
+      TraitItem::Fn(ref mut method) => {
+        let kind = take_stub_kind(method)?;
+        if method.default.is_some() && kind.is_none() {
+          continue;
+        }
+        entries.push(stub_entry(method, kind)?);
+      },
+      TraitItem::Const(ref constant) if constant.default.is_some() => {},
+      ref other => {
+        return Err(syn::Error::new_spanned(other, "#[stubbable] can only stub methods and defaulted consts"));
+      },
+    }
+  }
+  Ok(entries)
+}
+
+/// Removes the `#[stub(Kind)]` attribute from `method`, returning the kind it names.
+fn take_stub_kind(method: &mut TraitItemFn) -> syn::Result<Option<Ident>> {
+  let mut kind = None;
+  let mut attrs = Vec::new();
+  for attr in method.attrs.drain(..) {
+    if !attr.path().is_ident("stub") {
+      attrs.push(attr);
+      continue;
+    }
+    let ident = attr.parse_args::<Ident>()?;
+    if !STUB_KINDS.iter().any(|known| ident == known) {
+      return Err(syn::Error::new_spanned(&ident, format!("Expected one of {}", STUB_KINDS.join(", "))));
+    }
+    kind = Some(ident);
+  }
+  method.attrs = attrs;
+  Ok(kind)
+}
+
+fn stub_entry(method: &TraitItemFn, kind: Option<Ident>) -> syn::Result<TokenStream2> {
+  let sig = &method.sig;
+  let fn_ident = &sig.ident;
+  if sig.constness.is_some() || sig.asyncness.is_some() || sig.unsafety.is_some() || sig.abi.is_some()
+    || sig.variadic.is_some() {
+    return Err(syn::Error::new_spanned(sig, format!("#[stubbable] can't stub [{}]; give it a default body", fn_ident)));
+  }
+  if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+    return Err(syn::Error::new_spanned(&sig.generics,
+      format!("#[stubbable] can't stub generic method [{}]; give it a default body", fn_ident)));
+  }
+
+  let mut receiver = None;
+  let mut arg_idents = Vec::new();
+  let mut arg_types = Vec::new();
+  for (idx, input) in sig.inputs.iter().enumerate() {
+    match *input {
+      FnArg::Receiver(ref recv) => receiver = Some(receiver_tokens(recv)?),
+      FnArg::Typed(ref pat_type) => {
+        if TypeScan::of(&pat_type.ty).impl_trait {
+          return Err(syn::Error::new_spanned(&pat_type.ty,
+            format!("#[stubbable] can't stub [{}], which takes an impl Trait arg", fn_ident)));
+        }
+        arg_idents.push(match *pat_type.pat {
+          Pat::Ident(ref pat) => pat.ident.clone(),
+          _ => format_ident!("arg{}", idx),
+        });
+        arg_types.push(&*pat_type.ty);
+      },
+    }
+  }
+
+  let ret_type = match sig.output {
+    ReturnType::Default => quote!(()),
+    ReturnType::Type(_, ref ty) => quote!(#ty),
+  };
+  let kind = match kind {
+    Some(kind) => kind,
+    None => {
+      if let ReturnType::Type(_, ref ty) = sig.output {
+        let scan = TypeScan::of(ty);
+        if scan.borrows || scan.impl_trait {
+          return Err(syn::Error::new_spanned(ty, format!(
+            "#[stubbable] can't hand out the return type of [{}]; mark it #[stub(nostub)] or give it a default body",
+            fn_ident)));
+        }
+      }
+      let recordable = |ty: &&Type| {
+        is_clone_and_eq(ty) || owned_form(ty).is_some_and(|owned| is_clone_and_eq(&owned))
+      };
+      if arg_types.iter().all(recordable) {
+        format_ident!("ArgWatchingStub")
+      } else {
+        format_ident!("InterceptingStub")
+      }
+    },
+  };
+
+  let args = arg_idents.iter().zip(arg_types).map(|(ident, ty)| {
+    match owned_form(ty) {
+      Some(ref owned) if kind == "ArgWatchingStub" || kind == "Stub" => quote!(#ident: #ty => #owned),
+      _ => quote!(#ident: #ty),
+    }
+  }).collect::<Vec<_>>();
+
+  Ok(match receiver {
+    Some(receiver) => quote! {
+      {#kind: #fn_ident (#receiver #(, #args)*) -> #ret_type}
+    },
+    None => quote! {
+      {#kind: #fn_ident (#(#args),*) -> #ret_type}
+    },
+  })
+}
+
+/// The owned type an `ArgWatchingStub` records for a shared reference: `String` for `&str`,
+/// `Vec<T>` for `&[T]` and `T` for any other `&T`.
+fn owned_form(ty: &Type) -> Option<Type> {
+  let reference = match *ty {
+    Type::Reference(ref reference) if reference.mutability.is_none() => reference,
+    _ => return None,
+  };
+  match *reference.elem {
+    Type::Path(ref path) if path.qself.is_none() && path.path.is_ident("str") => Some(parse_quote!(String)),
+    Type::Slice(ref slice) => {
+      let elem = &slice.elem;
+      Some(parse_quote!(Vec<#elem>))
+    },
+    ref elem => Some(elem.clone()),
+  }
+}
+
+fn receiver_tokens(recv: &Receiver) -> syn::Result<TokenStream2> {
+  if recv.colon_token.is_some() {
+    return Err(syn::Error::new_spanned(recv, "#[stubbable] can't stub methods with a typed self"));
+  }
+  match recv.reference {
+    Some((_, Some(ref lifetime))) => {
+      Err(syn::Error::new_spanned(lifetime, "#[stubbable] can't stub methods with a named self lifetime"))
+    },
+    Some((_, None)) if recv.mutability.is_some() => Ok(quote!(&mut self)),
+    Some((_, None)) => Ok(quote!(&self)),
+    None if recv.mutability.is_some() => Ok(quote!(mut self)),
+    None => Ok(quote!(self)),
+  }
+}
+
+/// Whether `ty` is one the macro knows to be `Clone + PartialEq`.
+///
+/// Traits can't be queried from a macro, so anything outside std's primitives, `String` and the
+/// common containers is assumed not to be.
+fn is_clone_and_eq(ty: &Type) -> bool {
+  const PRIMITIVES: &[&str] = &[
+    "bool", "char", "String", "f32", "f64",
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+  ];
+  const CONTAINERS: &[&str] = &[
+    "Option", "Result", "Vec", "VecDeque", "Box", "Rc", "Arc", "BTreeMap", "BTreeSet", "HashMap", "HashSet",
+  ];
+
+  match *ty {
+    Type::Paren(ref paren) => is_clone_and_eq(&paren.elem),
+    Type::Group(ref group) => is_clone_and_eq(&group.elem),
+    Type::Array(ref array) => is_clone_and_eq(&array.elem),
+    Type::Tuple(ref tuple) => tuple.elems.iter().all(is_clone_and_eq),
+    Type::Path(ref path) if path.qself.is_none() => {
+      let segment = match path.path.segments.last() {
+        Some(segment) => segment,
+        None => return false,
+      };
+      match segment.arguments {
+        PathArguments::None => PRIMITIVES.iter().any(|known| segment.ident == known),
+        PathArguments::AngleBracketed(ref generics) => {
+          CONTAINERS.iter().any(|known| segment.ident == known) && generics.args.iter().all(|arg| match *arg {
+            GenericArgument::Type(ref ty) => is_clone_and_eq(ty),
+            _ => false,
+          })
+        },
+        PathArguments::Parenthesized(_) => false,
+      }
+    },
+    _ => false,
+  }
+}
+
+/// What a type mentions that a stub field can't hold.
+#[derive(Default)]
+struct TypeScan {
+  borrows: bool,
+  impl_trait: bool,
+}
+
+impl TypeScan {
+  fn of(ty: &Type) -> TypeScan {
+    let mut scan = TypeScan::default();
+    scan.visit_type(ty);
+    scan
+  }
+}
+
+impl<'ast> Visit<'ast> for TypeScan {
+  fn visit_type_reference(&mut self, reference: &'ast TypeReference) {
+    if reference.lifetime.is_none() {
+      self.borrows = true;
+    }
+    syn::visit::visit_type_reference(self, reference);
+  }
+
+  fn visit_lifetime(&mut self, lifetime: &'ast Lifetime) {
+    if lifetime.ident != "static" {
+      self.borrows = true;
+    }
+  }
+
+  fn visit_type_impl_trait(&mut self, impl_trait: &'ast TypeImplTrait) {
+    self.impl_trait = true;
+    syn::visit::visit_type_impl_trait(self, impl_trait);
+  }
+}
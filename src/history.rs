@@ -0,0 +1,132 @@
+//! Scoping a stub's call history to the calls made after some point in a test.
+//!
+//! Every recorded call takes the next number from one process-wide sequence, so a `Checkpoint` is
+//! just the number the next call will get. That makes a checkpoint taken from one stub, or from a
+//! whole stub struct, valid for every other stub too.
+
+use std::hash::Hash;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{ArgHistogram, CallCount, CallCountError, CallWatcher};
+use matchers::{self, Matcher};
+
+static CALL_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// A point in a test; `since` views only the calls made after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Checkpoint(usize);
+
+impl Checkpoint {
+  pub fn now() -> Checkpoint {
+    Checkpoint(CALL_SEQ.load(Ordering::SeqCst))
+  }
+
+  /// The number of the first call in `seqs` made after this checkpoint, or `seqs.len()`.
+  pub fn first_call_in(&self, seqs: &[usize]) -> usize {
+    seqs.partition_point(|&seq| seq < self.0)
+  }
+}
+
+/// Takes the sequence number for a call as it begins.
+pub fn next_seq() -> usize {
+  CALL_SEQ.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Inserts `seq` into the sorted `seqs` and returns where it went, so calls stay in the order they
+/// began even when one finishes inside another.
+pub fn insert_seq(seqs: &mut Vec<usize>, seq: usize) -> usize {
+  let pos = seqs.partition_point(|&call_seq| call_seq < seq);
+  seqs.insert(pos, seq);
+  pos
+}
+
+/// The calls a stub recorded after a checkpoint, holding the stub's call history borrowed (or, for
+/// sync stubs, locked) until dropped.
+pub struct CallsSince<Calls> {
+  pub name: Option<&'static str>,
+  pub calls: Calls,
+  pub first: usize,
+}
+
+impl<Args, Calls: Deref<Target = Vec<Args>>> CallsSince<Calls> {
+  /// The args of every call since the checkpoint, in call order.
+  pub fn recorded_args(&self) -> &[Args] {
+    &self.calls[self.first..]
+  }
+
+  /// The args of call number `call`, counting from the checkpoint.
+  pub fn get_args_for_call(&self, call: usize) -> Option<Args> where Args: Clone {
+    self.recorded_args().get(call).cloned()
+  }
+
+  pub fn was_called_with_args(&self, args: &Args) -> bool where Args: PartialEq {
+    self.recorded_args().iter().any(|call_args| call_args == args)
+  }
+
+  pub fn never_called_with_args(&self, args: &Args) -> bool where Args: PartialEq {
+    !self.was_called_with_args(args)
+  }
+
+  pub fn was_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    self.recorded_args().iter().any(|call_args| matcher.matches(call_args))
+  }
+
+  pub fn never_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    !self.was_called_matching(matcher)
+  }
+
+  /// Counts only the calls since the checkpoint whose args satisfy `matcher`.
+  pub fn calls_matching<M: Matcher<Args>>(&self, matcher: M) -> MatchingCalls {
+    MatchingCalls::count(self.name, self.recorded_args(), matcher)
+  }
+
+  /// Counts only the calls since the checkpoint with exactly `args`.
+  pub fn calls_with_args(&self, args: &Args) -> MatchingCalls where Args: PartialEq {
+    self.calls_matching(matchers::predicate(|call_args: &Args| call_args == args))
+  }
+
+  pub fn call_count_for(&self, args: &Args) -> u32 where Args: PartialEq {
+    self.calls_with_args(args).count
+  }
+
+  pub fn call_histogram(&self) -> ArgHistogram<Args> where Args: Clone + Eq + Hash {
+    ArgHistogram::by_hash(self.recorded_args())
+  }
+
+  pub fn call_histogram_by_eq(&self) -> ArgHistogram<Args> where Args: Clone + PartialEq {
+    ArgHistogram::by_eq(self.recorded_args())
+  }
+}
+
+impl<Args, Calls: Deref<Target = Vec<Args>>> CallWatcher for CallsSince<Calls> {
+  fn call_count(&self) -> u32 { self.recorded_args().len() as u32 }
+  fn stub_name(&self) -> Option<&'static str> { self.name }
+}
+
+/// How many of a stub's calls had args satisfying some matcher, counted when it was made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchingCalls {
+  pub name: Option<&'static str>,
+  pub count: u32,
+}
+
+impl MatchingCalls {
+  pub fn count<Args, M: Matcher<Args>>(name: Option<&'static str>, calls: &[Args], matcher: M) -> MatchingCalls {
+    let count = calls.iter().filter(|args| matcher.matches(args)).count() as u32;
+    MatchingCalls { name, count }
+  }
+}
+
+impl CallWatcher for MatchingCalls {
+  fn call_count(&self) -> u32 { self.count }
+  fn stub_name(&self) -> Option<&'static str> { self.name }
+
+  fn check_called(&self, count: CallCount) -> Result<(), CallCountError> {
+    if count.is_met_by(self.count) {
+      Ok(())
+    } else {
+      Err(CallCountError { name: self.name, expected: count, actual: self.count, matching_args: true })
+    }
+  }
+}
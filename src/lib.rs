@@ -1,94 +1,931 @@
-#![cfg_attr(feature = "nightly", feature(type_macros))]
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
-use std::cell::{Cell, RefCell};
+extern crate rust_stub_derive;
+
+#[doc(hidden)]
+pub mod probe;
+pub mod matchers;
+mod history;
+mod report;
+mod static_stubs;
+mod stats;
+mod sync;
+
+pub use history::{CallsSince, Checkpoint, MatchingCalls};
+pub use matchers::Matcher;
+pub use static_stubs::{reset_all_static_stubs, reset_static_stubs, static_stub, StaticStubsGuard};
+#[doc(hidden)]
+pub use static_stubs::StaticCall;
+pub use stats::{ArgHistogram, ArgHistogramIter};
+pub use sync::{SyncArgWatchingStub, SyncInterceptingStub, SyncSimpleStub, SyncStub};
+pub use rust_stub_derive::stubbable;
 
 pub trait CallWatcher { fn call_count(&self) -> u32;
   fn was_called_n_times(&self, times: u32) -> bool { self.call_count() == times }
   fn was_called_once(&self) -> bool { self.was_called_n_times(1) }
   fn was_called(&self) -> bool { self.call_count() != 0 }
+  fn was_never_called(&self) -> bool { !self.was_called() }
+  fn was_called_at_least(&self, times: u32) -> bool { self.call_count() >= times }
+  fn was_called_at_most(&self, times: u32) -> bool { self.call_count() <= times }
+  fn was_called_between(&self, times: RangeInclusive<u32>) -> bool { times.contains(&self.call_count()) }
+
+  /// The name of the stubbed method, once the stub has been called.
+  fn stub_name(&self) -> Option<&'static str> { None }
+
+  /// Checks the call count against `count`, describing the mismatch if there is one.
+  fn check_called(&self, count: CallCount) -> Result<(), CallCountError> {
+    let calls = self.call_count();
+    if count.is_met_by(calls) {
+      Ok(())
+    } else {
+      Err(CallCountError { name: self.stub_name(), expected: count, actual: calls, matching_args: false })
+    }
+  }
+
+  /// Panics with the method name and actual call count unless it was called exactly `times` times.
+  #[track_caller]
+  fn verify_called_times(&self, times: u32) {
+    self.verify_called(CallCount::Exactly(times))
+  }
+
+  /// Panics with the method name and actual call count unless the call count meets `count`.
+  #[track_caller]
+  fn verify_called(&self, count: CallCount) {
+    if let Err(err) = self.check_called(count) {
+      panic!("{}", err)
+    }
+  }
+}
+
+/// A bound on how many times a stub expects to be called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallCount {
+  Exactly(u32),
+  AtLeast(u32),
+  AtMost(u32),
+  /// Between the two counts, inclusive.
+  Between(u32, u32),
+}
+
+impl CallCount {
+  pub fn is_met_by(&self, calls: u32) -> bool {
+    match *self {
+      CallCount::Exactly(times) => calls == times,
+      CallCount::AtLeast(times) => calls >= times,
+      CallCount::AtMost(times) => calls <= times,
+      CallCount::Between(min, max) => min <= calls && calls <= max,
+    }
+  }
+}
+
+impl From<RangeInclusive<u32>> for CallCount {
+  fn from(times: RangeInclusive<u32>) -> CallCount {
+    CallCount::Between(*times.start(), *times.end())
+  }
+}
+
+impl fmt::Display for CallCount {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      CallCount::Exactly(times) => write!(f, "exactly {} times", times),
+      CallCount::AtLeast(times) => write!(f, "at least {} times", times),
+      CallCount::AtMost(times) => write!(f, "at most {} times", times),
+      CallCount::Between(min, max) => write!(f, "between {} and {} times", min, max),
+    }
+  }
+}
+
+/// A stub whose call count didn't meet a `CallCount`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallCountError {
+  pub name: Option<&'static str>,
+  pub expected: CallCount,
+  pub actual: u32,
+  /// Whether only calls with matching args were counted.
+  pub matching_args: bool,
+}
+
+impl fmt::Display for CallCountError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let name = self.name.unwrap_or("unnamed stub");
+    if self.matching_args {
+      write!(f, "Expected [{}] to be called with matching args {}, but {} of its calls matched",
+             name, self.expected, self.actual)
+    } else {
+      write!(f, "Expected [{}] to be called {}, but it was called {} times", name, self.expected, self.actual)
+    }
+  }
+}
+
+impl Error for CallCountError {}
+
+/// Expectations registered up front and checked when the stub is dropped.
+pub trait CallExpecter: CallWatcher {
+  fn expect(&mut self, count: CallCount);
+  fn unmet_expectations(&self) -> Vec<String>;
+
+  fn expect_calls(&mut self, times: u32) { self.expect(CallCount::Exactly(times)) }
+  fn expect_at_least(&mut self, times: u32) { self.expect(CallCount::AtLeast(times)) }
+  fn expect_at_most(&mut self, times: u32) { self.expect(CallCount::AtMost(times)) }
+  fn expect_never(&mut self) { self.expect(CallCount::Exactly(0)) }
+
+  /// Panics with every unmet expectation. Stubs call this when dropped, unless the thread is
+  /// already panicking.
+  fn verify(&self) {
+    let unmet = self.unmet_expectations();
+    if !unmet.is_empty() {
+      panic!("{}", unmet.join("\n"))
+    }
+  }
+}
+
+/// Reports the unmet expectations of several stubs together. A stub reporting into the group hands
+/// its unmet expectations over when it is dropped instead of panicking, and the group panics with
+/// all of them when it is dropped itself. `create_stub!` structs keep one after their stub fields,
+/// so it is dropped last.
+pub struct ExpectationGroup {
+  deferred: DeferredExpectations,
+}
+
+/// A stub's link to the `ExpectationGroup` it reports into.
+#[derive(Clone)]
+pub struct DeferredExpectations {
+  unmet: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl ExpectationGroup {
+  pub fn new() -> ExpectationGroup {
+    ExpectationGroup { deferred: DeferredExpectations { unmet: Arc::new(Mutex::new(Some(Vec::new()))) } }
+  }
+
+  pub fn deferred(&self) -> DeferredExpectations {
+    self.deferred.clone()
+  }
+}
+
+impl Default for ExpectationGroup {
+  fn default() -> ExpectationGroup { ExpectationGroup::new() }
+}
+
+impl Drop for ExpectationGroup {
+  fn drop(&mut self) {
+    let unmet = lock(&self.deferred.unmet).take().unwrap_or_default();
+    if !unmet.is_empty() && !thread::panicking() {
+      panic!("{}", unmet.join("\n"))
+    }
+  }
+}
+
+impl DeferredExpectations {
+  /// Hands `unmet` to the group, or back if the group was already dropped.
+  pub fn defer(&self, unmet: Vec<String>) -> Result<(), Vec<String>> {
+    match *lock(&self.unmet) {
+      Some(ref mut deferred) => {
+        deferred.extend(unmet);
+        Ok(())
+      }
+      None => Err(unmet),
+    }
+  }
+}
+
+/// Locks `mutex`, ignoring poisoning so a panic on one thread doesn't hide a stub's history.
+fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn unmet_call_counts(name: Option<&'static str>, expected: &[CallCount], calls: u32) -> Vec<String> {
+  expected.iter()
+    .filter(|count| !count.is_met_by(calls))
+    .map(|&count| CallCountError { name, expected: count, actual: calls, matching_args: false }.to_string())
+    .collect()
+}
+
+/// A log of calls, in the order they were made, shared by every stub reporting into it.
+#[derive(Clone, Default)]
+pub struct CallJournal {
+  pub calls: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl CallJournal {
+  pub fn new() -> CallJournal { CallJournal::default() }
+
+  pub fn record(&self, fn_name: &'static str) {
+    lock(&self.calls).push(fn_name);
+  }
+
+  pub fn calls(&self) -> Vec<&'static str> {
+    lock(&self.calls).clone()
+  }
+
+  /// Panics unless calls to `expected` were made in this relative order. Other calls may be
+  /// interleaved.
+  pub fn in_order(&self, expected: &[&str]) {
+    let calls = lock(&self.calls);
+    let mut next_seq = 0;
+    for (idx, fn_name) in expected.iter().enumerate() {
+      match calls[next_seq..].iter().position(|call| call == fn_name) {
+        Some(offset) => next_seq += offset + 1,
+        None => {
+          let after = match idx {
+            0 => String::new(),
+            _ => format!(" after [{}] (call #{})", expected[idx - 1], next_seq - 1)
+          };
+          let seen = calls.iter().enumerate()
+            .filter(|&(_, call)| call == fn_name)
+            .map(|(seq, _)| format!("#{}", seq))
+            .collect::<Vec<_>>();
+          let reason = match seen.len() {
+            0 => "it was never called".to_owned(),
+            _ => format!("it was only called at {}", seen.join(", "))
+          };
+          panic!("Expected [{}] to be called{}, but {}. Calls were: {}", fn_name, after, reason, list_calls(&calls))
+        }
+      }
+    }
+  }
+
+  /// Panics unless the first call to `first` was made before the first call to `second`.
+  pub fn called_before(&self, first: &str, second: &str) {
+    let calls = lock(&self.calls);
+    let first_seq = calls.iter().position(|call| *call == first);
+    let second_seq = calls.iter().position(|call| *call == second);
+    match (first_seq, second_seq) {
+      (Some(a), Some(b)) if a < b => (),
+      (None, _) => panic!("Expected [{}] to be called before [{}], but [{}] was never called. Calls were: {}", first, second, first, list_calls(&calls)),
+      (_, None) => panic!("Expected [{}] to be called before [{}], but [{}] was never called. Calls were: {}", first, second, second, list_calls(&calls)),
+      (Some(a), Some(b)) => panic!("Expected [{}] to be called before [{}], but [{}] (call #{}) came before [{}] (call #{}). Calls were: {}", first, second, second, b, first, a, list_calls(&calls)),
+    }
+  }
+}
+
+impl fmt::Display for CallJournal {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", list_calls(&lock(&self.calls)))
+  }
+}
+
+fn list_calls(calls: &[&str]) -> String {
+  if calls.is_empty() {
+    return "(none)".to_owned()
+  }
+  calls.iter().enumerate()
+    .map(|(seq, call)| format!("#{} {}", seq, call))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// Stubs that can report each call into a `CallJournal` shared with other stubs.
+pub trait Journaled {
+  fn report_to(&mut self, journal: &CallJournal);
+}
+
+/// Stubs that know the name of the method they stand in for, for failure messages.
+pub trait Named {
+  /// Names the stub before its first call, which otherwise names it.
+  fn set_name(&mut self, fn_name: &'static str);
 }
 
 pub trait ReturnStubber<T> {
   fn returns(&mut self, val: T);
 }
 
-pub struct SimpleStub<T: Clone> {
-  pub return_val: Option<T>,
-  pub call_count: Cell<u32>
+/// Return stubbing beyond a single value, for the stubs in this crate. Unlike `returns`, it is
+/// also available for return types that aren't `Clone`.
+pub trait ReturnStubberExt<T> {
+  /// Queues `val` to be handed out by exactly one call. A matching `when` rule still takes
+  /// priority, but a queued value comes ahead of every other configured return.
+  fn returns_once(&mut self, val: T);
+  /// Hands out `vals` one per call, then follows `when_exhausted`.
+  fn returns_in_order(&mut self, vals: Vec<T>) where T: Clone;
+  fn when_exhausted(&mut self, behavior: Exhausted);
+  /// Returns `T::default()` instead of panicking once no configured return value is left.
+  fn lenient(&mut self) where T: Default;
+  /// Undoes `lenient`, so calls without a configured return value panic again (unless `T` is `()`).
+  fn strict(&mut self);
+}
+
+/// What a stub hands out once every value given to `returns_in_order` has been used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exhausted {
+  RepeatLast,
+  Cycle,
+  Panic,
+}
+
+pub type ReturnFn<Args, T> = Rc<dyn Fn(&Args) -> T>;
+pub type KeyFn<Args> = Box<dyn Fn(&Args) -> String>;
+
+/// Where a stub's return values come from: values queued with `returns_once` first, then either
+/// the repeated `returns`/`returns_in_order` values or the `returns_from` producer.
+///
+/// Only the repeated values need `T: Clone`, so `set` captures `T::clone` for `next_value` to use.
+pub struct ReturnValues<T, Producer: ?Sized = dyn Fn() -> T> {
+  pub once: Mutex<VecDeque<T>>,
+  pub values: Vec<T>,
+  pub clone: Option<fn(&T) -> T>,
+  pub producer: Option<Box<Producer>>,
+  pub served: AtomicUsize,
+  pub exhausted: Exhausted,
+  pub fallback: Option<fn() -> T>,
 }
 
-pub struct ArgWatchingStub<T: Clone, Args> {
-  pub return_val: Option<T>,
-  pub call_args: RefCell<Vec<Args>>
+/// Returns `value` for any call whose arguments satisfy `matcher`.
+pub struct ReturnRule<Args, T> {
+  pub matcher: Box<dyn Matcher<Args>>,
+  pub value: T,
+  pub clone: fn(&T) -> T,
 }
 
-pub struct InterceptingStub<T: Clone, Interceptor: ?Sized> {
-  pub return_val: Option<T>,
-  pub call_interceptor: Option<Box<Interceptor>>,
-  pub call_count: Cell<u32>,
+/// A stub for one method, built from layers a test opts into:
+///
+/// * recording args: every call's `Args` are kept, so with `Args = ()` only calls are counted;
+/// * recording returns: `record_returns` keeps a clone of every value handed out;
+/// * intercepting: an interceptor set with `set_interceptor` or `intercept_once` sees each call
+///   first, and what it returns decides the call's return value. With `dyn FnMut(A) -> T` it is the
+///   interceptor's result, with `dyn FnMut(A) -> Option<T>` it is that result unless `None`, and
+///   with `dyn FnMut(A)` it is always the configured return;
+/// * returning: rules from `when`, then values queued with `returns_once`, then `returns_with`,
+///   `returns_from` or `returns`/`returns_in_order`.
+///
+/// `SimpleStub`, `ArgWatchingStub` and `InterceptingStub` name the common combinations. A `Stub:`
+/// method in `create_stub!` uses all of them: it records clones of its args (or their owned form,
+/// with `=>`) and passes the originals to a `dyn FnMut(A) -> Option<T>` interceptor.
+///
+/// `Recording` is `IgnoredArgs` for the stubs that record `()` whatever their method's args, which
+/// leaves out the methods that pick returns or keys by args: `returns_with`, `when`,
+/// `when_matching`, `when_args`, `record_keys` and `record_debug`.
+pub struct Stub<Args, T, Interceptor: ?Sized = dyn FnMut(), Recording = RecordedArgs> {
+  pub name: Rc<Cell<Option<&'static str>>>,
+  pub journal: Option<CallJournal>,
+  pub return_vals: ReturnValues<T>,
+  pub return_fn: Option<ReturnFn<Args, T>>,
+  pub return_rules: Vec<ReturnRule<Args, T>>,
+  pub call_interceptor: RefCell<Option<Box<Interceptor>>>,
+  pub once_interceptors: RefCell<VecDeque<Box<Interceptor>>>,
+  pub call_args: Rc<RefCell<Vec<Args>>>,
+  pub call_seqs: Rc<RefCell<Vec<usize>>>,
+  pub key_fn: Option<KeyFn<Args>>,
+  pub call_keys: Rc<RefCell<Vec<Option<String>>>>,
+  pub return_clone: Option<fn(&T) -> T>,
+  pub call_returns: Rc<RefCell<Vec<T>>>,
+  pub return_seqs: Rc<RefCell<Vec<usize>>>,
+  pub expected_calls: Vec<CallCount>,
+  pub expected_args: Vec<Box<dyn Matcher<Args>>>,
+  pub expectation_group: Option<DeferredExpectations>,
+  pub recording: PhantomData<Recording>,
 }
 
-impl<T: Clone> SimpleStub<T> {
-  pub fn new() -> SimpleStub<T> {
-    SimpleStub {
-      return_val: None,
-      call_count: Cell::new(0)
+/// Marks a stub whose recorded args are the call's args.
+pub struct RecordedArgs;
+
+/// Marks a stub that records `()` for every call, whatever the method's args.
+pub struct IgnoredArgs;
+
+/// A stub that counts calls and hands out return values.
+pub type SimpleStub<T> = Stub<(), T, dyn FnMut(), IgnoredArgs>;
+
+/// A stub that also records every call's args.
+pub type ArgWatchingStub<T, Args> = Stub<Args, T>;
+
+/// A stub passing each call's args to an interceptor closure, e.g. `dyn FnMut(&str) -> Option<u32>`.
+pub type InterceptingStub<T, Interceptor> = Stub<(), T, Interceptor, IgnoredArgs>;
+
+impl<T, Producer: ?Sized> ReturnValues<T, Producer> {
+  pub fn new() -> ReturnValues<T, Producer> {
+    ReturnValues {
+      once: Mutex::new(VecDeque::new()),
+      values: Vec::new(),
+      clone: None,
+      producer: None,
+      served: AtomicUsize::new(0),
+      exhausted: Exhausted::RepeatLast,
+      fallback: None,
     }
   }
+
+  pub fn set(&mut self, vals: Vec<T>) where T: Clone {
+    self.clear();
+    self.values = vals;
+    self.clone = Some(T::clone);
+  }
+
+  pub fn set_producer(&mut self, producer: Box<Producer>) {
+    self.clear();
+    self.producer = Some(producer);
+  }
+
+  /// Forgets the repeated values and the producer, but not values queued with `push_once`.
+  pub fn clear(&mut self) {
+    self.values = Vec::new();
+    self.clone = None;
+    self.producer = None;
+    self.served.store(0, Ordering::SeqCst);
+  }
+
+  pub fn push_once(&mut self, val: T) {
+    lock(&self.once).push_back(val);
+  }
+
+  pub fn next_once(&self) -> Option<T> {
+    lock(&self.once).pop_front()
+  }
 }
 
-impl<T: Clone, Args> ArgWatchingStub<T, Args> {
-  pub fn new() -> ArgWatchingStub<T, Args> {
-    ArgWatchingStub {
-      return_val: None,
-      call_args: RefCell::new(Vec::new())
+impl<T, Producer: ?Sized + Fn() -> T> ReturnValues<T, Producer> {
+  /// Hands out the next configured value, or the fallback (if any) when nothing was configured.
+  pub fn next_value(&self, fn_name: &str) -> Option<T> {
+    if let Some(val) = self.next_once() {
+      return Some(val)
     }
+    if let Some(ref producer) = self.producer {
+      return Some(producer())
+    }
+    let clone = match self.clone {
+      Some(clone) if !self.values.is_empty() => clone,
+      _ => return self.fallback.map(|fallback| fallback()),
+    };
+    let served = self.served.fetch_add(1, Ordering::SeqCst);
+    let len = self.values.len();
+    let idx = if served < len {
+      served
+    } else {
+      match self.exhausted {
+        Exhausted::RepeatLast => len - 1,
+        Exhausted::Cycle => served % len,
+        Exhausted::Panic => panic!("All {} return values for [{}] were used up", len, fn_name),
+      }
+    };
+    Some(clone(&self.values[idx]))
   }
 }
 
-impl<T: Clone, Interceptor: ?Sized> InterceptingStub<T, Interceptor> {
-  pub fn new() -> InterceptingStub<T, Interceptor> {
-    InterceptingStub {
-      return_val: None,
-      call_interceptor: None,
-      call_count: Cell::new(0),
+impl<T, Producer: ?Sized> Default for ReturnValues<T, Producer> {
+  fn default() -> ReturnValues<T, Producer> { ReturnValues::new() }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  pub fn new() -> Stub<Args, T, Interceptor, Recording> {
+    Stub {
+      name: Rc::new(Cell::new(None)),
+      journal: None,
+      return_vals: ReturnValues::new(),
+      return_fn: None,
+      return_rules: Vec::new(),
+      call_interceptor: RefCell::new(None),
+      once_interceptors: RefCell::new(VecDeque::new()),
+      call_args: Rc::new(RefCell::new(Vec::new())),
+      call_seqs: Rc::new(RefCell::new(Vec::new())),
+      key_fn: None,
+      call_keys: Rc::new(RefCell::new(Vec::new())),
+      return_clone: None,
+      call_returns: Rc::new(RefCell::new(Vec::new())),
+      return_seqs: Rc::new(RefCell::new(Vec::new())),
+      expected_calls: Vec::new(),
+      expected_args: Vec::new(),
+      expectation_group: None,
+      recording: PhantomData,
+    }
+  }
+
+  /// A stub already named `fn_name`, so expectations on it name the method even if it is never
+  /// called.
+  pub fn named(fn_name: &'static str) -> Stub<Args, T, Interceptor, Recording> {
+    let stub = Stub::new();
+    stub.name.set(Some(fn_name));
+    stub
+  }
+
+  pub fn record_call(&self, fn_name: &'static str, args: Args) {
+    let seq = self.begin_call(fn_name);
+    self.finish_call(seq, args);
+  }
+
+  /// Names the stub and journals a call as it starts, so calls its interceptor or return value
+  /// makes to other stubs are journaled after it. Returns the call's sequence number, for
+  /// `finish_call`.
+  pub fn begin_call(&self, fn_name: &'static str) -> usize {
+    self.name.set(Some(fn_name));
+    if let Some(ref journal) = self.journal {
+      journal.record(fn_name);
+    }
+    history::next_seq()
+  }
+
+  /// Records the args of the call `begin_call` numbered `seq`. Calls are recorded in the order they
+  /// began, as in the journal, so a call made from inside another call's interceptor comes after it.
+  pub fn finish_call(&self, seq: usize, args: Args) {
+    let pos = history::insert_seq(&mut self.call_seqs.borrow_mut(), seq);
+    self.call_keys.borrow_mut().insert(pos, self.key_fn.as_ref().map(|key_fn| key_fn(&args)));
+    self.call_args.borrow_mut().insert(pos, args);
+  }
+
+  /// Forgets every recorded call, with its args, key and return value, here and in every handle.
+  pub fn reset_calls(&self) {
+    self.call_args.borrow_mut().clear();
+    self.call_seqs.borrow_mut().clear();
+    self.call_keys.borrow_mut().clear();
+    self.call_returns.borrow_mut().clear();
+    self.return_seqs.borrow_mut().clear();
+  }
+
+  /// Puts the stub back the way `new` made it, except for its name and journal: forgets its calls,
+  /// return values, rules, interceptors, recorded keys and returns, and expectations.
+  pub fn reset_all(&mut self) {
+    self.reset_calls();
+    self.return_vals = ReturnValues::new();
+    self.return_fn = None;
+    self.return_rules.clear();
+    *self.call_interceptor.get_mut() = None;
+    self.once_interceptors.get_mut().clear();
+    self.key_fn = None;
+    self.return_clone = None;
+    self.expected_calls.clear();
+    self.expected_args.clear();
+  }
+
+  /// Leaves the stub's unmet expectations for `group` to report when the stub is dropped.
+  pub fn report_expectations_to(&mut self, group: &ExpectationGroup) {
+    self.expectation_group = Some(group.deferred());
+  }
+
+  /// Marks the calls made so far, for `since`. Same as `Checkpoint::now()`.
+  pub fn checkpoint(&self) -> Checkpoint {
+    Checkpoint::now()
+  }
+
+  /// Views the calls made after `checkpoint`, e.g. `stub.f.since(setup).was_called_once()`. Like
+  /// `recorded_args`, the view must be dropped before the stub is called again.
+  pub fn since(&self, checkpoint: Checkpoint) -> CallsSince<Ref<'_, Vec<Args>>> {
+    CallsSince {
+      name: self.name.get(),
+      first: checkpoint.first_call_in(&self.call_seqs.borrow()),
+      calls: self.call_args.borrow(),
+    }
+  }
+
+  /// Keeps a value the stub returned. Spies record a clone of every return value that has one.
+  pub fn record_return(&self, val: T) {
+    self.insert_return(history::next_seq(), val);
+  }
+
+  fn insert_return(&self, seq: usize, val: T) {
+    let pos = history::insert_seq(&mut self.return_seqs.borrow_mut(), seq);
+    self.call_returns.borrow_mut().insert(pos, val);
+  }
+
+  /// Keeps a clone of every value the stub hands out from now on.
+  pub fn record_returns(&mut self) where T: Clone {
+    self.return_clone = Some(T::clone);
+  }
+
+  /// Records a clone of `val` as the return value of the call numbered `seq`, if `record_returns`
+  /// was called.
+  pub fn observe_return(&self, seq: usize, val: &T) {
+    if let Some(clone) = self.return_clone {
+      self.insert_return(seq, clone(val));
+    }
+  }
+
+  /// Returns a stub sharing this stub's call history, which stays observable after this stub is
+  /// moved or dropped, e.g. by a self-consuming method.
+  pub fn handle(&self) -> Stub<Args, T, Interceptor, Recording> {
+    Stub {
+      name: self.name.clone(),
+      journal: None,
+      return_vals: ReturnValues::new(),
+      return_fn: None,
+      return_rules: Vec::new(),
+      call_interceptor: RefCell::new(None),
+      once_interceptors: RefCell::new(VecDeque::new()),
+      call_args: self.call_args.clone(),
+      call_seqs: self.call_seqs.clone(),
+      key_fn: None,
+      call_keys: self.call_keys.clone(),
+      return_clone: None,
+      call_returns: self.call_returns.clone(),
+      return_seqs: self.return_seqs.clone(),
+      expected_calls: Vec::new(),
+      expected_args: Vec::new(),
+      expectation_group: None,
+      recording: PhantomData,
+    }
+  }
+
+  /// Sets the closure each call is passed to. It may be `FnMut` (the generated stubs declare
+  /// `dyn FnMut`), so it can keep its own state without a `RefCell`.
+  pub fn set_interceptor(&mut self, f: Box<Interceptor>) {
+    *self.call_interceptor.get_mut() = Some(f)
+  }
+
+  /// Queues `f` to intercept exactly one call, ahead of the interceptor set with `set_interceptor`.
+  ///
+  /// Queued interceptors run before the return value is produced, so they can write through
+  /// `&mut` args, e.g. `Box::new(|buf: &mut [u8]| buf[..2].copy_from_slice(b"ok"))` fills the
+  /// next call's buffer.
+  pub fn intercept_once(&mut self, f: Box<Interceptor>) {
+    self.once_interceptors.get_mut().push_back(f);
+  }
+
+  /// Runs `call` on the next queued interceptor, or else on the interceptor set with
+  /// `set_interceptor`, and returns its result.
+  ///
+  /// The interceptor is taken out of the stub while it runs, so a call it makes back into this
+  /// stub is counted and answered as usual but isn't intercepted a second time. It is put back
+  /// afterwards even if it panics.
+  pub fn intercept<R, F: FnOnce(&mut Interceptor) -> R>(&self, call: F) -> Option<R> {
+    let once = self.once_interceptors.borrow_mut().pop_front();
+    if let Some(mut interceptor) = once {
+      return Some(call(&mut interceptor));
+    }
+    let mut taken = TakenInterceptor {
+      interceptor: self.call_interceptor.borrow_mut().take(),
+      put_back: |interceptor| *self.call_interceptor.borrow_mut() = Some(interceptor),
+    };
+    let interceptor = taken.interceptor.as_mut()?;
+    Some(call(interceptor))
+  }
+
+  /// Builds a fresh return value for every call, replacing any other unconditional return. For
+  /// return types that can't be cloned.
+  pub fn returns_from<F: Fn() -> T + 'static>(&mut self, f: F) {
+    self.return_fn = None;
+    self.return_vals.set_producer(Box::new(f));
+  }
+
+  pub fn next_return(&self, fn_name: &str, args: &Args) -> Option<T> {
+    if let Some(val) = self.priority_return(args) {
+      return Some(val)
     }
+    match self.return_fn {
+      Some(ref f) => Some(f(args)),
+      None => self.return_vals.next_value(fn_name)
+    }
+  }
+
+  /// The return value from a matching `when` rule or `returns_once`, which come ahead of the rest.
+  fn priority_return(&self, args: &Args) -> Option<T> {
+    let rule = self.return_rules.iter().rev().find(|rule| rule.matcher.matches(args));
+    if let Some(rule) = rule {
+      return Some((rule.clone)(&rule.value))
+    }
+    self.return_vals.next_once()
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized> Stub<Args, T, Interceptor> {
+  /// Computes the return value from the call arguments, replacing any fixed return values.
+  ///
+  /// A `SimpleStub` or `InterceptingStub` records no args to pass, so it has no `returns_with`; an
+  /// `InterceptingStub` computes its return from the args with an interceptor returning `T` (or
+  /// `Option<T>`) instead.
+  pub fn returns_with<F: Fn(&Args) -> T + 'static>(&mut self, f: F) {
+    self.return_vals.clear();
+    self.return_fn = Some(Rc::new(f));
+  }
+
+  /// Returns `val` whenever the call arguments satisfy `matcher`. Rules added later take
+  /// precedence over earlier ones, and every rule takes precedence over `returns` and
+  /// `returns_with`, which act as the fallback.
+  ///
+  /// Rules match the args the stub records, so only an `ArgWatchingStub` or a `Stub:` method, which
+  /// records borrowed args in owned form, has them. An `InterceptingStub` matches its args with an
+  /// interceptor returning `Option<T>`: `Some` answers the call, and `None` falls through to the
+  /// configured returns.
+  pub fn when<F: Fn(&Args) -> bool + 'static>(&mut self, matcher: F, val: T) where T: Clone {
+    self.when_matching(matchers::predicate(matcher), val)
+  }
+
+  pub fn when_matching<M: Matcher<Args> + 'static>(&mut self, matcher: M, val: T) where T: Clone {
+    self.return_rules.push(ReturnRule { matcher: Box::new(matcher), value: val, clone: T::clone });
+  }
+
+  /// Also keeps `key_fn(&args)` for every later call, for args that can't be cloned or compared.
+  pub fn record_keys<F: Fn(&Args) -> String + 'static>(&mut self, key_fn: F) {
+    self.key_fn = Some(Box::new(key_fn));
   }
 }
 
-impl<T: Clone> ReturnStubber<T> for SimpleStub<T> {
-  fn returns(&mut self, val: T) { self.return_val = Some(val); }
+impl<Args, T, Interceptor: ?Sized, Recording> Default for Stub<Args, T, Interceptor, Recording> {
+  fn default() -> Stub<Args, T, Interceptor, Recording> { Stub::new() }
 }
 
-impl<T: Clone, Args> ReturnStubber<T> for ArgWatchingStub<T, Args> {
-  fn returns(&mut self, val: T) { self.return_val = Some(val); }
+impl<Args, T: Clone, Interceptor: ?Sized, Recording> ReturnStubber<T> for Stub<Args, T, Interceptor, Recording> {
+  fn returns(&mut self, val: T) { self.returns_in_order(vec![val]); }
 }
 
-impl<T: Clone, Interceptor: ?Sized> ReturnStubber<T> for InterceptingStub<T, Interceptor> {
-  fn returns(&mut self, val: T) { self.return_val = Some(val); }
+impl<Args, T, Interceptor: ?Sized, Recording> ReturnStubberExt<T> for Stub<Args, T, Interceptor, Recording> {
+  fn returns_once(&mut self, val: T) { self.return_vals.push_once(val); }
+  fn returns_in_order(&mut self, vals: Vec<T>) where T: Clone {
+    self.return_fn = None;
+    self.return_vals.set(vals);
+  }
+  fn when_exhausted(&mut self, behavior: Exhausted) { self.return_vals.exhausted = behavior; }
+  fn lenient(&mut self) where T: Default { self.return_vals.fallback = Some(T::default); }
+  fn strict(&mut self) { self.return_vals.fallback = None; }
 }
 
-impl<T: Clone, Args> CallWatcher for ArgWatchingStub<T, Args> {
+impl<Args, T, Interceptor: ?Sized, Recording> CallWatcher for Stub<Args, T, Interceptor, Recording> {
   fn call_count(&self) -> u32 { self.call_args.borrow().len() as u32 }
+  fn stub_name(&self) -> Option<&'static str> { self.name.get() }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> CallExpecter for Stub<Args, T, Interceptor, Recording> {
+  fn expect(&mut self, count: CallCount) { self.expected_calls.push(count); }
+  fn unmet_expectations(&self) -> Vec<String> {
+    let mut unmet = unmet_call_counts(self.name.get(), &self.expected_calls, self.call_count());
+    let call_args = self.call_args.borrow();
+    for matcher in &self.expected_args {
+      if !call_args.iter().any(|args| matcher.matches(args)) {
+        unmet.push(format!("Expected [{}] to be called with matching args, but none of its {} calls matched",
+                           self.name.get().unwrap_or("unnamed stub"), call_args.len()));
+      }
+    }
+    unmet
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Journaled for Stub<Args, T, Interceptor, Recording> {
+  fn report_to(&mut self, journal: &CallJournal) { self.journal = Some(journal.clone()); }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Named for Stub<Args, T, Interceptor, Recording> {
+  fn set_name(&mut self, fn_name: &'static str) { self.name.set(Some(fn_name)); }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Drop for Stub<Args, T, Interceptor, Recording> {
+  fn drop(&mut self) {
+    if let Some(ref group) = self.expectation_group {
+      if group.defer(self.unmet_expectations()).is_ok() { return }
+    }
+    if !thread::panicking() { self.verify() }
+  }
 }
 
-impl<T: Clone> CallWatcher for SimpleStub<T> {
-  fn call_count(&self) -> u32 { self.call_count.get() }
+/// Hands an interceptor taken out of its stub to `put_back` once it returns or panics.
+struct TakenInterceptor<Interceptor: ?Sized, F: FnMut(Box<Interceptor>)> {
+  interceptor: Option<Box<Interceptor>>,
+  put_back: F,
 }
 
-impl<T: Clone, Interceptor: ?Sized> CallWatcher for InterceptingStub<T, Interceptor> {
-  fn call_count(&self) -> u32 { self.call_count.get() }
+impl<Interceptor: ?Sized, F: FnMut(Box<Interceptor>)> Drop for TakenInterceptor<Interceptor, F> {
+  fn drop(&mut self) {
+    if let Some(interceptor) = self.interceptor.take() {
+      (self.put_back)(interceptor);
+    }
+  }
 }
 
-impl<T: Clone, Args: Clone> ArgWatchingStub<T, Args> {
+impl<Args: Clone, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
   pub fn get_args_for_call(&self, call: usize) -> Option<Args> {
     self.call_args.borrow()
       .get(call)
-      .map(|val| val.clone())
+      .cloned()
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Borrows the args of every call so far, in call order, e.g. `stub.f.recorded_args().iter()`.
+  /// The borrow must end before the stub is called again.
+  pub fn recorded_args(&self) -> Ref<'_, [Args]> {
+    Ref::map(self.call_args.borrow(), |args| args.as_slice())
+  }
+
+  /// Runs `f` on the args of call number `call`, if there was one, without cloning them.
+  pub fn with_args_for_call<R, F: FnOnce(&Args) -> R>(&self, call: usize, f: F) -> Option<R> {
+    self.call_args.borrow().get(call).map(f)
+  }
+
+  /// Borrows every recorded return value, in call order.
+  pub fn recorded_returns(&self) -> Ref<'_, [T]> {
+    Ref::map(self.call_returns.borrow(), |returns| returns.as_slice())
+  }
+
+  pub fn return_for_call(&self, call: usize) -> Option<T> where T: Clone {
+    self.call_returns.borrow().get(call).cloned()
+  }
+
+  /// The key kept for call number `call`, if there was one and keys were being recorded.
+  pub fn key_for_call(&self, call: usize) -> Option<String> {
+    self.call_keys.borrow().get(call).and_then(|key| key.clone())
+  }
+
+  pub fn was_called_with_key(&self, key: &str) -> bool {
+    self.call_keys.borrow().iter().any(|call_key| call_key.as_ref().is_some_and(|call_key| call_key == key))
+  }
+}
+
+impl<Args: Clone + Eq + Hash, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// How many calls were made with each distinct args, in order of first call.
+  pub fn call_histogram(&self) -> ArgHistogram<Args> {
+    ArgHistogram::by_hash(&self.call_args.borrow())
+  }
+
+  /// Every distinct args the stub was called with, in order of first call.
+  pub fn distinct_args(&self) -> Vec<Args> {
+    self.call_histogram().distinct_args()
+  }
+}
+
+impl<Args: fmt::Debug, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Panics unless some call's args satisfy `matcher`, listing every call otherwise.
+  #[track_caller]
+  pub fn verify_called_matching<M: Matcher<Args>>(&self, matcher: M) {
+    let call_args = self.call_args.borrow();
+    if !call_args.iter().any(|args| matcher.matches(args)) {
+      panic!("{}", report::unmatched_calls(self.name.get(), &call_args))
+    }
+  }
+}
+
+impl<Args: PartialEq + fmt::Debug, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Panics unless some call had exactly `args`, listing every call and diffing the closest one
+  /// otherwise.
+  #[track_caller]
+  pub fn verify_called_with(&self, args: &Args) {
+    let call_args = self.call_args.borrow();
+    if !call_args.iter().any(|call_args| call_args == args) {
+      panic!("{}", report::unmatched_args(self.name.get(), args, &call_args))
+    }
+  }
+}
+
+impl<Args: fmt::Debug + 'static, T, Interceptor: ?Sized> Stub<Args, T, Interceptor> {
+  /// Keeps the `Debug` rendering of every later call's args as its key.
+  pub fn record_debug(&mut self) {
+    self.record_keys(|args| format!("{:?}", args))
+  }
+}
+
+impl<Args: PartialEq + 'static, T, Interceptor: ?Sized> Stub<Args, T, Interceptor> {
+  /// Returns `val` whenever the stub is called with exactly `args`.
+  pub fn when_args(&mut self, args: Args, val: T) where T: Clone {
+    self.when_matching(matchers::eq(args), val)
+  }
+}
+
+impl<Args: PartialEq + 'static, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Expects at least one call with exactly `args` by the time the stub is dropped.
+  pub fn expect_args(&mut self, args: Args) {
+    self.expect_matching(matchers::eq(args))
   }
 }
 
-impl<T: Clone, Args: PartialEq> ArgWatchingStub<T, Args> {
+impl<Args, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Expects at least one call matching `matcher` by the time the stub is dropped.
+  pub fn expect_matching<M: Matcher<Args> + 'static>(&mut self, matcher: M) {
+    self.expected_args.push(Box::new(matcher));
+  }
+
+  pub fn was_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    self.call_args.borrow()
+      .iter()
+      .any(|call_args| matcher.matches(call_args))
+  }
+
+  pub fn always_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    self.call_args.borrow()
+      .iter()
+      .all(|call_args| matcher.matches(call_args))
+  }
+
+  pub fn never_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    !self.was_called_matching(matcher)
+  }
+
+  /// Counts only the calls whose args satisfy `matcher`, e.g.
+  /// `stub.f.calls_matching((eq(1), any())).was_called_n_times(2)`.
+  pub fn calls_matching<M: Matcher<Args>>(&self, matcher: M) -> MatchingCalls {
+    MatchingCalls::count(self.name.get(), &self.call_args.borrow(), matcher)
+  }
+}
+
+impl<Args: PartialEq, T, Interceptor: ?Sized, Recording> Stub<Args, T, Interceptor, Recording> {
+  /// Counts only the calls with exactly `args`.
+  pub fn calls_with_args(&self, args: &Args) -> MatchingCalls {
+    self.calls_matching(matchers::predicate(|call_args: &Args| call_args == args))
+  }
+
+  /// The number of calls made with exactly `args`.
+  pub fn call_count_for(&self, args: &Args) -> u32 {
+    self.calls_with_args(args).count
+  }
+
+  /// Groups the calls by args without hashing them, for args that aren't `Eq + Hash`.
+  pub fn call_histogram_by_eq(&self) -> ArgHistogram<Args> where Args: Clone {
+    ArgHistogram::by_eq(&self.call_args.borrow())
+  }
+
   pub fn was_called_with_args(&self, args: &Args) -> bool {
     self.call_args.borrow()
       .iter()
@@ -109,114 +946,279 @@ impl<T: Clone, Args: PartialEq> ArgWatchingStub<T, Args> {
   }
 }
 
-impl<T: Clone, Interceptor: ?Sized> InterceptingStub<T, Interceptor> {
-  pub fn set_interceptor(&mut self, f: Box<Interceptor>) {
-    self.call_interceptor = Some(f)
-  }
-}
-
+/// Implements one trait method from the stub field of the same name, or for static methods from
+/// `static_stub`. Every stub kind shares one body per receiver, and differs only in which args it
+/// records and which it passes to the interceptor.
 #[macro_export]
 macro_rules! impl_helper {
-  (ArgWatchingStub: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (&self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          let mut args = self.$fn_ident.call_args.borrow_mut();
-          args.push(($($arg_ident),*));
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+  (nostub: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+    fn $fn_ident (&self, $(_: $arg_type),*) -> $ret_type {
+      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
     }
   };
-  (InterceptingStub: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (&self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          match self.$fn_ident.call_interceptor {
-            Some(ref method) => method($($arg_ident),*),
-            None => ()
-          }
-          self.$fn_ident.call_count.set(1 + self.$fn_ident.call_count.get());
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+  (nostub: $fn_ident:ident (&mut self $(,$arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+    fn $fn_ident (&mut self, $(_: $arg_type),*) -> $ret_type {
+      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
     }
   };
-  (SimpleStub: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+  (nostub: $fn_ident:ident (self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+    fn $fn_ident (self, $(_: $arg_type),*) -> $ret_type {
+      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
+    }
+  };
+  (nostub: $fn_ident:ident (mut self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+    fn $fn_ident (self, $(_: $arg_type),*) -> $ret_type {
+      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
+    }
+  };
+  (nostub: $fn_ident:ident ($($arg_ident:ident: $arg_type:ty),*) -> $ret_type:ty) => {
+    fn $fn_ident ($(_: $arg_type),*) -> $ret_type {
+      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
+    }
+  };
+  ($kind:ident: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
     #[allow(unused_variables)]
     fn $fn_ident (&self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          self.$fn_ident.call_count.set(1 + self.$fn_ident.call_count.get());
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+      $crate::stub_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*) -> $ret_type)
     }
   };
-  (ArgWatchingStub: $fn_ident:ident (&mut self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+  ($kind:ident: $fn_ident:ident (&mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    #[allow(unused_variables)]
     fn $fn_ident (&mut self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          let mut args = self.$fn_ident.call_args.borrow_mut();
-          args.push(($($arg_ident),*));
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+      $crate::stub_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*) -> $ret_type)
     }
   };
-  (InterceptingStub: $fn_ident:ident (&mut self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (&mut self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          match self.$fn_ident.call_interceptor {
-            Some(ref method) => method($($arg_ident),*),
-            None => ()
-          }
-          self.$fn_ident.call_count.set(1 + self.$fn_ident.call_count.get());
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+  ($kind:ident: $fn_ident:ident (self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    #[allow(unused_variables)]
+    fn $fn_ident (self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::stub_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*) -> $ret_type)
     }
   };
-  (SimpleStub: $fn_ident:ident (&mut self $(,$arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
+  ($kind:ident: $fn_ident:ident (mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    #[allow(unused_mut, unused_variables)]
+    fn $fn_ident (mut self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::stub_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*) -> $ret_type)
+    }
+  };
+  ($kind:ident: $fn_ident:ident ($($arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?),*) -> $ret_type:ty) => {
     #[allow(unused_variables)]
-    fn $fn_ident (&mut self, $($arg_ident: $arg_type),*) -> $ret_type {
-      match self.$fn_ident.return_val.clone() {
-        Some(val) => {
-          self.$fn_ident.call_count.set(1 + self.$fn_ident.call_count.get());
-          val
-        },
-        _ => panic!("#returns was not called on [{}] prior to invocation", stringify!($fn_ident))
-      }
+    fn $fn_ident ($($arg_ident: $arg_type),*) -> $ret_type {
+      let stub = $crate::static_stub::<Self, $crate::build_stub_type!($kind ($($arg_type $(=> $owned)?),*) -> $ret_type)>(
+        stringify!($fn_ident));
+      $crate::stub_call!($kind *stub, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*) -> $ret_type)
     }
   };
-  (nostub: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (&self, $(_: $arg_type),*) -> $ret_type {
-      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
+}
+
+/// The body of a stubbed method: journal the call, intercept, pick the return value, then record
+/// the call's args. `$stub` is a stub, or for static methods the `RefCell` holding one.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! stub_call {
+  ($kind:ident $stub:expr, $fn_ident:ident ($($arg_ident:ident $(=> $owned:ty $(= $conv:expr)?)?),*) -> $ret_type:ty) => {{
+    #[allow(unused_imports)]
+    use $crate::StaticCall;
+    let stub = &$stub;
+    let seq = stub.begin_call(stringify!($fn_ident));
+    let args = $crate::call_args!($kind $($arg_ident $(=> $owned $(= $conv)?)?),*);
+    let intercepted = $crate::intercept_call!($kind stub ($($arg_ident),*));
+    let val = match $crate::intercepted_return!(intercepted, $ret_type)
+      .or_else(|| stub.next_return(stringify!($fn_ident), &args)) {
+      Some(val) => val,
+      None => $crate::unconfigured_return!($ret_type, stringify!($fn_ident)),
+    };
+    stub.finish_call(seq, args);
+    stub.observe_return(seq, &val);
+    val
+  }}
+}
+
+/// The args a stub kind records: none for `SimpleStub` and `InterceptingStub`, the args themselves
+/// (or their owned form) for `ArgWatchingStub`, and clones of them for `Stub`, which still passes
+/// the originals to its interceptor.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! call_args {
+  (ArgWatchingStub $($arg_ident:ident $(=> $owned:ty $(= $conv:expr)?)?),*) => {
+    ($($crate::recorded_arg!($arg_ident $(=> $owned $(= $conv)?)?)),*)
+  };
+  (Stub $($arg_ident:ident $(=> $owned:ty $(= $conv:expr)?)?),*) => {
+    ($($crate::spied_arg!($arg_ident $(=> $owned $(= $conv)?)?)),*)
+  };
+  ($kind:ident $($rest:tt)*) => { () };
+}
+
+/// Runs a stub's interceptor, passing it the call's args unless the kind records them instead.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! intercept_call {
+  (SimpleStub $stub:ident ($($arg_ident:ident),*)) => { $stub.intercept(|method| method()) };
+  (ArgWatchingStub $stub:ident ($($arg_ident:ident),*)) => { $stub.intercept(|method| method()) };
+  ($kind:ident $stub:ident ($($arg_ident:ident),*)) => { $stub.intercept(|method| method($($arg_ident),*)) };
+}
+
+/// The type an `ArgWatchingStub` records for an arg declared as `name: Type` or `name: Type => Owned`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! recorded_type {
+  ($arg_type:ty) => { $arg_type };
+  ($arg_type:ty => $owned:ty) => { $owned };
+}
+
+/// Converts an arg to the form its `ArgWatchingStub` records: as is, via `ToOwned`, or via the
+/// conversion given after `=`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! recorded_arg {
+  ($arg_ident:ident) => { $arg_ident };
+  ($arg_ident:ident => $owned:ty) => {{
+    let owned: $owned = ::std::borrow::ToOwned::to_owned($arg_ident);
+    owned
+  }};
+  ($arg_ident:ident => $owned:ty = $conv:expr) => {{
+    let owned: $owned = ($conv)($arg_ident);
+    owned
+  }};
+}
+
+/// Converts a spied arg to the form its `ArgWatchingStub` records, leaving the arg itself to be
+/// forwarded: a clone, or the owned form given after `=>`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! spied_arg {
+  ($arg_ident:ident) => { ::std::clone::Clone::clone(&$arg_ident) };
+  ($arg_ident:ident => $($owned:tt)*) => { $crate::recorded_arg!($arg_ident => $($owned)*) };
+}
+
+/// The return value an interceptor's output stands for, if any: `None` for `()`, the output itself
+/// for `Option<$ret_type>`, and `Some` of it for `$ret_type`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! intercepted_return {
+  ($out:ident, $ret_type:ty) => {{
+    #[allow(unused_imports)]
+    use $crate::probe::{FallibleIntercept, ObservingIntercept, ValueIntercept};
+    (&$crate::probe::InterceptProbe::<_, $ret_type>::of(&$out)).returned($out)
+  }}
+}
+
+/// Produces the return value for a call with nothing configured: `()` for unit methods, otherwise a
+/// panic asking for `#returns`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! unconfigured_return {
+  ($ret_type:ty, $fn_name:expr) => {{
+    #[allow(unused_imports)]
+    use $crate::probe::{MissingReturn, UnitReturn};
+    (&$crate::probe::ReturnProbe::<$ret_type>::new()).unconfigured($fn_name)
+  }}
+}
+
+/// The arms `instrument_stub!` uses in spy mode, forwarding each call to the `$inner` field. Every
+/// receiver shares the `spy_call!` body, and static methods are stubbed as usual.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! spy_helper {
+  ($tr8:ident $inner:ident $kind:ident: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    fn $fn_ident (&self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::spy_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*)
+        => $tr8::$fn_ident(&self.$inner $(, $arg_ident)*))
     }
   };
-  (nostub: $fn_ident:ident (&mut self $(,$arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (&mut self, $(_: $arg_type),*) -> $ret_type {
-      panic!("Method [{}] was not stubbed", stringify!($fn_ident))
+  ($tr8:ident $inner:ident $kind:ident: $fn_ident:ident (&mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    fn $fn_ident (&mut self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::spy_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*)
+        => $tr8::$fn_ident(&mut self.$inner $(, $arg_ident)*))
     }
   };
-  (nostub: $fn_ident:ident (self $(, $arg_ident:ident: $arg_type:ty)*) -> $ret_type:ty) => {
-    fn $fn_ident (self, $(_: $arg_type),*) -> $ret_type {
-      panic!("Method [{}] was not stubbed and self-consuming methods cannot currently be stubbed", stringify!($fn_ident))
+  ($tr8:ident $inner:ident $kind:ident: $fn_ident:ident (self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    fn $fn_ident (self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::spy_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*)
+        => $tr8::$fn_ident(self.$inner $(, $arg_ident)*))
     }
   };
-  (nostub: $fn_ident:ident ($($arg_ident:ident: $arg_type:ty),*) -> $ret_type:ty) => {
-    fn $fn_ident ($(_: $arg_type),*) -> $ret_type {
-      panic!("Method [{}] was not stubbed and static methods cannot currently be stubbed", stringify!($fn_ident))
+  ($tr8:ident $inner:ident $kind:ident: $fn_ident:ident (mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty) => {
+    #[allow(unused_mut)]
+    fn $fn_ident (mut self, $($arg_ident: $arg_type),*) -> $ret_type {
+      $crate::spy_call!($kind self.$fn_ident, $fn_ident ($($arg_ident $(=> $owned $(= $conv)?)?),*)
+        => $tr8::$fn_ident(self.$inner $(, $arg_ident)*))
     }
   };
+  ($tr8:ident $inner:ident $($e:tt)*) => {
+    $crate::impl_helper!($($e)*);
+  };
+}
+
+/// The body of a spied method: `$forward` makes the real call. `ArgWatchingStub` methods record
+/// their args (cloned, or in the owned form given after `=>`) first, and `SimpleStub` methods only
+/// count the call; both keep the return value if `record_returns` was called. `nostub` methods
+/// forward without recording anything. `InterceptingStub` and `Stub` methods hand their args to an
+/// interceptor, which a spy can't do as well as forwarding them, so they are rejected.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! spy_call {
+  (nostub $stub:expr, $fn_ident:ident ($($arg_ident:ident $(=> $owned:ty $(= $conv:expr)?)?),*) => $forward:expr) => {
+    $forward
+  };
+  (ArgWatchingStub $stub:expr, $fn_ident:ident ($($arg_ident:ident $(=> $owned:ty $(= $conv:expr)?)?),*) => $forward:expr) => {{
+    let stub = &$stub;
+    let seq = stub.begin_call(stringify!($fn_ident));
+    let args = ($($crate::spied_arg!($arg_ident $(=> $owned $(= $conv)?)?)),*);
+    let val = $forward;
+    stub.finish_call(seq, args);
+    stub.observe_return(seq, &val);
+    val
+  }};
+  (SimpleStub $stub:expr, $fn_ident:ident ($($arg_ident:ident),*) => $forward:expr) => {{
+    let stub = &$stub;
+    let seq = stub.begin_call(stringify!($fn_ident));
+    let val = $forward;
+    stub.finish_call(seq, ());
+    stub.observe_return(seq, &val);
+    val
+  }};
+  ($kind:ident $stub:expr, $fn_ident:ident ($($args:tt)*) => $forward:expr) => {
+    compile_error!(concat!("a spy can't forward [", stringify!($fn_ident), "] (", stringify!($kind),
+                           ":); spies take ArgWatchingStub:, SimpleStub: and nostub: methods"))
+  };
 }
 
+/// The arms `instrument_stub!` uses in delegating mode: `nostub` methods forward to the `$fallback`
+/// field, and everything else is stubbed as usual.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! delegate_helper {
+  ($tr8:ident $fallback:ident nostub: $fn_ident:ident (&self $($e:tt)*) -> $ret_type:ty) => {
+    $crate::spy_helper!($tr8 $fallback nostub: $fn_ident (&self $($e)*) -> $ret_type);
+  };
+  ($tr8:ident $fallback:ident nostub: $fn_ident:ident (&mut self $($e:tt)*) -> $ret_type:ty) => {
+    $crate::spy_helper!($tr8 $fallback nostub: $fn_ident (&mut self $($e)*) -> $ret_type);
+  };
+  ($tr8:ident $fallback:ident nostub: $fn_ident:ident (self $($e:tt)*) -> $ret_type:ty) => {
+    $crate::spy_helper!($tr8 $fallback nostub: $fn_ident (self $($e)*) -> $ret_type);
+  };
+  ($tr8:ident $fallback:ident nostub: $fn_ident:ident (mut self $($e:tt)*) -> $ret_type:ty) => {
+    $crate::spy_helper!($tr8 $fallback nostub: $fn_ident (mut self $($e)*) -> $ret_type);
+  };
+  ($tr8:ident $fallback:ident $($e:tt)*) => {
+    $crate::impl_helper!($($e)*);
+  };
+}
+
+/// Implements `$tr8` for a stub struct, answering each method from the stub field of the same name.
+///
+/// The `$new_type as $tr8 spying $inner` form makes a spy instead: each method forwards to the
+/// `$tr8` implementation held in the `$inner` field, and records the call on its stub field.
+/// `ArgWatchingStub` methods record their args (which must be `Clone`, or recorded in owned form
+/// with `=>`), and after `record_returns` every method with a stub field keeps the value returned.
+/// `nostub` methods forward without recording, and static methods are stubbed as usual.
+/// `InterceptingStub` and `Stub` methods can't be spied on.
+///
+/// The `$new_type as $tr8 delegating $fallback` form makes a partial stub: `nostub` methods that
+/// take `self` forward to the `$tr8` implementation held in the `$fallback` field rather than
+/// panicking, and the listed stub methods behave as usual. Static `nostub` methods have no field to
+/// forward to and still panic. Nothing else forwards: methods with a default body can be left out of
+/// the list to keep it, and every other method must be listed.
 #[macro_export]
 macro_rules! instrument_stub {
   (
@@ -225,51 +1227,227 @@ macro_rules! instrument_stub {
     }
   ) => {
     impl $tr8 for $new_type {
-      $(impl_helper!($($e)*);)*
+      $($crate::impl_helper!($($e)*);)*
     }
-  }
+  };
+  (
+    $new_type:ty as $tr8:ident delegating $fallback:ident {
+      $({$($e:tt)*})*
+    }
+  ) => {
+    impl $tr8 for $new_type {
+      $($crate::delegate_helper!($tr8 $fallback $($e)*);)*
+    }
+  };
+  (
+    $new_type:ty as $tr8:ident spying $inner:ident {
+      $({$($e:tt)*})*
+    }
+  ) => {
+    impl $tr8 for $new_type {
+      $($crate::spy_helper!($tr8 $inner $($e)*);)*
+    }
+  };
+}
 
+/// Panics with the call history unless `$stub` was called with exactly `$args`.
+#[macro_export]
+macro_rules! assert_called_with {
+  ($stub:expr, $args:expr) => {
+    $stub.verify_called_with(&$args)
+  };
 }
 
-#[cfg(feature = "nightly")]
-#[macro_use]
-mod type_macros {
+/// Panics with the actual call count unless `$stub` was called exactly `$times` times.
+#[macro_export]
+macro_rules! assert_called_times {
+  ($stub:expr, $times:expr) => {
+    $crate::CallWatcher::verify_called_times(&$stub, $times)
+  };
+}
 
-  #[macro_export]
-  macro_rules! build_stub_type {
-    (ArgWatchingStub ($($arg_type:ty),*) -> $ret_type:ty) => {
-      ArgWatchingStub<$ret_type, ($($arg_type),*)>
-    };
-    (SimpleStub ($($arg_type:ty),*) -> $ret_type:ty) => {
-      SimpleStub<$ret_type>
-    };
-    (InterceptingStub ($($arg_type:ty),*) -> $ret_type:ty) => {
-      InterceptingStub<$ret_type, Fn($($arg_type),*)>
-    };
-  }
+#[macro_export]
+macro_rules! build_stub_type {
+  (ArgWatchingStub ($($arg_type:ty $(=> $owned:ty)?),*) -> $ret_type:ty) => {
+    $crate::ArgWatchingStub<$ret_type, ($($crate::recorded_type!($arg_type $(=> $owned)?)),*)>
+  };
+  (SimpleStub ($($arg_type:ty $(=> $owned:ty)?),*) -> $ret_type:ty) => {
+    $crate::SimpleStub<$ret_type>
+  };
+  (InterceptingStub ($($arg_type:ty $(=> $owned:ty)?),*) -> $ret_type:ty) => {
+    $crate::InterceptingStub<$ret_type, dyn FnMut($($arg_type),*)>
+  };
+  (Stub ($($arg_type:ty $(=> $owned:ty)?),*) -> $ret_type:ty) => {
+    $crate::Stub<($($crate::recorded_type!($arg_type $(=> $owned)?)),*), $ret_type, dyn FnMut($($arg_type),*) -> Option<$ret_type>>
+  };
+}
 
-  #[macro_export]
-  macro_rules! create_stub {
-    (
-      $new_type:ident {
-        $({$stub_ty:ident: $fn_ident:ident $($e:tt)*})*
+/// Declares a stub struct with one stub field per method.
+///
+/// The `$new_type as $tr8` form takes the same method list as `instrument_stub!` and also
+/// generates the trait impl, plus a `Journaled` impl reporting every field into one journal. The
+/// stubs report their unmet expectations together: dropping the struct, or calling its `verify`,
+/// panics once with all of them.
+/// `nostub` and static methods get no field. A leading visibility, as in `pub TraitStub as Trait`,
+/// applies to the struct, its fields and `new`.
+///
+/// `$new_type as $tr8 delegating fallback: Fallback` also adds a `fallback` field that `new` takes,
+/// and forwards the `nostub` methods that take `self` to it.
+///
+/// An `ArgWatchingStub` arg written `name: &str => String` is recorded in owned form via `ToOwned`,
+/// or via the conversion after `=`, as in `config: &Config => String = config_name`. The same
+/// syntax works in `instrument_stub!`.
+#[macro_export]
+macro_rules! create_stub {
+  (
+    $vis:vis $new_type:ident as $tr8:ident {
+      $({$($e:tt)*})*
+    }
+  ) => {
+    $crate::create_stub_struct!(@fields ($vis) () $new_type [] [] $({$($e)*})*);
+
+    $crate::instrument_stub! {
+      $new_type as $tr8 {
+        $({$($e)*})*
       }
-    ) => {
-      struct $new_type {
-        $($fn_ident: build_stub_type!($stub_ty $($e)*)),*
+    }
+  };
+  (
+    $vis:vis $new_type:ident as $tr8:ident delegating $fallback:ident: $fallback_ty:ty {
+      $({$($e:tt)*})*
+    }
+  ) => {
+    $crate::create_stub_struct!(@fields ($vis) ($fallback: $fallback_ty) $new_type [] [] $({$($e)*})*);
+
+    $crate::instrument_stub! {
+      $new_type as $tr8 delegating $fallback {
+        $({$($e)*})*
       }
+    }
+  };
+  (
+    $new_type:ident {
+      $({$stub_ty:ident: $fn_ident:ident $($e:tt)*})*
+    }
+  ) => {
+    struct $new_type {
+      $($fn_ident: $crate::build_stub_type!($stub_ty $($e)*)),*
+    }
 
-      impl $new_type {
-        fn new() -> $new_type {
-          $new_type {
-            $($fn_ident: $stub_ty::new()),*
-          }
+    impl $new_type {
+      fn new() -> $new_type {
+        $new_type {
+          $($fn_ident: $crate::$stub_ty::new()),*
         }
       }
     }
   }
 }
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! create_stub_struct {
+  (@fields ($vis:vis) ($($fallback:ident: $fallback_ty:ty)?) $new_type:ident [$($field:tt)*]
+   [$($field_ident:ident)*]) => {
+    $vis struct $new_type {
+      $($vis $fallback: $fallback_ty,)?
+      $($field)*
+      expectation_group: $crate::ExpectationGroup,
+    }
+
+    impl $new_type {
+      #[allow(unused_mut)]
+      $vis fn new($($fallback: $fallback_ty)?) -> $new_type {
+        let mut stub = $new_type {
+          $($fallback,)?
+          $($field_ident: Default::default(),)*
+          expectation_group: $crate::ExpectationGroup::new(),
+        };
+        $($crate::Named::set_name(&mut stub.$field_ident, stringify!($field_ident));)*
+        $(stub.$field_ident.report_expectations_to(&stub.expectation_group);)*
+        stub
+      }
+
+      /// Panics once with the unmet expectations of every stub. Dropping the struct checks them
+      /// the same way.
+      #[allow(unused_mut)]
+      $vis fn verify(&self) {
+        let mut unmet: Vec<String> = Vec::new();
+        $(unmet.extend($crate::CallExpecter::unmet_expectations(&self.$field_ident));)*
+        if !unmet.is_empty() {
+          panic!("{}", unmet.join("\n"))
+        }
+      }
+
+      /// Makes every stub whose return type implements `Default` fall back to `T::default()`.
+      #[allow(unused_imports)]
+      $vis fn lenient(&mut self) {
+        use $crate::probe::{DefaultReturn, NoDefaultReturn};
+        $(self.$field_ident.return_vals.fallback =
+          (&$crate::probe::ReturnProbe::of(&self.$field_ident.return_vals)).fallback();)*
+      }
+
+      /// Undoes `lenient` for every stub.
+      $vis fn strict(&mut self) {
+        $(self.$field_ident.return_vals.fallback = None;)*
+      }
+
+      /// Forgets the calls recorded by every stub.
+      $vis fn reset_calls(&self) {
+        $(self.$field_ident.reset_calls();)*
+      }
+
+      /// Resets every stub to the way `new` made it.
+      $vis fn reset_all(&mut self) {
+        $(self.$field_ident.reset_all();)*
+      }
+
+      /// Marks the calls made so far on every stub, for their `since`.
+      $vis fn checkpoint(&self) -> $crate::Checkpoint {
+        $crate::Checkpoint::now()
+      }
+    }
+
+    impl $crate::Journaled for $new_type {
+      #[allow(unused_variables)]
+      fn report_to(&mut self, journal: &$crate::CallJournal) {
+        $($crate::Journaled::report_to(&mut self.$field_ident, journal);)*
+      }
+    }
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {nostub: $($e:tt)*} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type [$($field)*] [$($field_ident)*] $($rest)*);
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {$stub_ty:ident: $fn_ident:ident (&self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type
+      [$($field)* $vis $fn_ident: $crate::build_stub_type!($stub_ty ($($arg_type $(=> $owned)?),*) -> $ret_type),]
+      [$($field_ident)* $fn_ident] $($rest)*);
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {$stub_ty:ident: $fn_ident:ident (&mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type
+      [$($field)* $vis $fn_ident: $crate::build_stub_type!($stub_ty ($($arg_type $(=> $owned)?),*) -> $ret_type),]
+      [$($field_ident)* $fn_ident] $($rest)*);
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {$stub_ty:ident: $fn_ident:ident (self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type
+      [$($field)* $vis $fn_ident: $crate::build_stub_type!($stub_ty ($($arg_type $(=> $owned)?),*) -> $ret_type),]
+      [$($field_ident)* $fn_ident] $($rest)*);
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {$stub_ty:ident: $fn_ident:ident (mut self $(, $arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?)*) -> $ret_type:ty} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type
+      [$($field)* $vis $fn_ident: $crate::build_stub_type!($stub_ty ($($arg_type $(=> $owned)?),*) -> $ret_type),]
+      [$($field_ident)* $fn_ident] $($rest)*);
+  };
+  (@fields ($vis:vis) $delegate:tt $new_type:ident [$($field:tt)*] [$($field_ident:ident)*]
+   {$stub_ty:ident: $fn_ident:ident ($($arg_ident:ident: $arg_type:ty $(=> $owned:ty $(= $conv:expr)?)?),*) -> $ret_type:ty} $($rest:tt)*) => {
+    $crate::create_stub_struct!(@fields ($vis) $delegate $new_type [$($field)*] [$($field_ident)*] $($rest)*);
+  };
+}
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,178 @@
+//! Argument matchers for verifying calls and conditional returns on `ArgWatchingStub`.
+//!
+//! A stub with several arguments records them as a tuple, so it is matched with a tuple of
+//! matchers, one per argument: `stub.f.was_called_matching((eq(1), any()))`.
+
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::hash::Hash;
+use std::ops::RangeBounds;
+
+pub trait Matcher<T: ?Sized> {
+  fn matches(&self, actual: &T) -> bool;
+}
+
+impl<T: ?Sized, M: Matcher<T> + ?Sized> Matcher<T> for &M {
+  fn matches(&self, actual: &T) -> bool { (**self).matches(actual) }
+}
+
+impl<T: ?Sized, M: Matcher<T> + ?Sized> Matcher<T> for Box<M> {
+  fn matches(&self, actual: &T) -> bool { (**self).matches(actual) }
+}
+
+pub struct AnyMatcher;
+pub struct EqMatcher<T>(pub T);
+pub struct NeMatcher<T>(pub T);
+pub struct LtMatcher<T>(pub T);
+pub struct LeMatcher<T>(pub T);
+pub struct GtMatcher<T>(pub T);
+pub struct GeMatcher<T>(pub T);
+pub struct InRangeMatcher<R>(pub R);
+pub struct PredicateMatcher<F>(pub F);
+pub struct ApproxMatcher { pub expected: f64, pub epsilon: f64 }
+pub struct ContainsMatcher<T>(pub T);
+pub struct AndMatcher<A, B>(pub A, pub B);
+pub struct OrMatcher<A, B>(pub A, pub B);
+pub struct NotMatcher<M>(pub M);
+
+/// Matches any value.
+pub fn any() -> AnyMatcher { AnyMatcher }
+
+pub fn eq<T>(expected: T) -> EqMatcher<T> { EqMatcher(expected) }
+
+pub fn ne<T>(unexpected: T) -> NeMatcher<T> { NeMatcher(unexpected) }
+
+pub fn lt<T>(bound: T) -> LtMatcher<T> { LtMatcher(bound) }
+
+pub fn le<T>(bound: T) -> LeMatcher<T> { LeMatcher(bound) }
+
+pub fn gt<T>(bound: T) -> GtMatcher<T> { GtMatcher(bound) }
+
+pub fn ge<T>(bound: T) -> GeMatcher<T> { GeMatcher(bound) }
+
+/// Matches values inside `range`, e.g. `in_range(1..=5)` or `in_range(10..)`.
+pub fn in_range<R>(range: R) -> InRangeMatcher<R> { InRangeMatcher(range) }
+
+/// Matches values for which `f` returns true.
+pub fn predicate<F>(f: F) -> PredicateMatcher<F> { PredicateMatcher(f) }
+
+/// Matches floats within `epsilon` of `expected`.
+pub fn approx(expected: f64, epsilon: f64) -> ApproxMatcher { ApproxMatcher { expected, epsilon } }
+
+/// Matches strings containing a substring or character, and collections containing an element.
+pub fn contains<T>(item: T) -> ContainsMatcher<T> { ContainsMatcher(item) }
+
+pub fn and<A, B>(a: A, b: B) -> AndMatcher<A, B> { AndMatcher(a, b) }
+
+pub fn or<A, B>(a: A, b: B) -> OrMatcher<A, B> { OrMatcher(a, b) }
+
+pub fn not<M>(m: M) -> NotMatcher<M> { NotMatcher(m) }
+
+impl<T: ?Sized> Matcher<T> for AnyMatcher {
+  fn matches(&self, _: &T) -> bool { true }
+}
+
+impl<T: PartialEq> Matcher<T> for EqMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual == self.0 }
+}
+
+impl<T: PartialEq> Matcher<T> for NeMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual != self.0 }
+}
+
+impl<T: PartialOrd> Matcher<T> for LtMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual < self.0 }
+}
+
+impl<T: PartialOrd> Matcher<T> for LeMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual <= self.0 }
+}
+
+impl<T: PartialOrd> Matcher<T> for GtMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual > self.0 }
+}
+
+impl<T: PartialOrd> Matcher<T> for GeMatcher<T> {
+  fn matches(&self, actual: &T) -> bool { *actual >= self.0 }
+}
+
+impl<T: PartialOrd, R: RangeBounds<T>> Matcher<T> for InRangeMatcher<R> {
+  fn matches(&self, actual: &T) -> bool { self.0.contains(actual) }
+}
+
+impl<T: ?Sized, F: Fn(&T) -> bool> Matcher<T> for PredicateMatcher<F> {
+  fn matches(&self, actual: &T) -> bool { (self.0)(actual) }
+}
+
+impl Matcher<f64> for ApproxMatcher {
+  fn matches(&self, actual: &f64) -> bool { (actual - self.expected).abs() <= self.epsilon }
+}
+
+impl Matcher<f32> for ApproxMatcher {
+  fn matches(&self, actual: &f32) -> bool { self.matches(&f64::from(*actual)) }
+}
+
+impl Matcher<str> for ContainsMatcher<&str> {
+  fn matches(&self, actual: &str) -> bool { actual.contains(self.0) }
+}
+
+impl Matcher<String> for ContainsMatcher<&str> {
+  fn matches(&self, actual: &String) -> bool { actual.contains(self.0) }
+}
+
+impl Matcher<str> for ContainsMatcher<char> {
+  fn matches(&self, actual: &str) -> bool { actual.contains(self.0) }
+}
+
+impl Matcher<String> for ContainsMatcher<char> {
+  fn matches(&self, actual: &String) -> bool { actual.contains(self.0) }
+}
+
+impl<T: PartialEq> Matcher<[T]> for ContainsMatcher<T> {
+  fn matches(&self, actual: &[T]) -> bool { actual.contains(&self.0) }
+}
+
+impl<T: PartialEq> Matcher<Vec<T>> for ContainsMatcher<T> {
+  fn matches(&self, actual: &Vec<T>) -> bool { actual.contains(&self.0) }
+}
+
+impl<T: PartialEq> Matcher<VecDeque<T>> for ContainsMatcher<T> {
+  fn matches(&self, actual: &VecDeque<T>) -> bool { actual.contains(&self.0) }
+}
+
+impl<T: Eq + Hash> Matcher<HashSet<T>> for ContainsMatcher<T> {
+  fn matches(&self, actual: &HashSet<T>) -> bool { actual.contains(&self.0) }
+}
+
+impl<T: Ord> Matcher<BTreeSet<T>> for ContainsMatcher<T> {
+  fn matches(&self, actual: &BTreeSet<T>) -> bool { actual.contains(&self.0) }
+}
+
+impl<T: ?Sized, A: Matcher<T>, B: Matcher<T>> Matcher<T> for AndMatcher<A, B> {
+  fn matches(&self, actual: &T) -> bool { self.0.matches(actual) && self.1.matches(actual) }
+}
+
+impl<T: ?Sized, A: Matcher<T>, B: Matcher<T>> Matcher<T> for OrMatcher<A, B> {
+  fn matches(&self, actual: &T) -> bool { self.0.matches(actual) || self.1.matches(actual) }
+}
+
+impl<T: ?Sized, M: Matcher<T>> Matcher<T> for NotMatcher<M> {
+  fn matches(&self, actual: &T) -> bool { !self.0.matches(actual) }
+}
+
+macro_rules! tuple_matcher {
+  ($($arg:ident: $matcher:ident: $idx:tt),*) => {
+    impl<$($arg,)* $($matcher: Matcher<$arg>),*> Matcher<($($arg,)*)> for ($($matcher,)*) {
+      fn matches(&self, actual: &($($arg,)*)) -> bool {
+        $(self.$idx.matches(&actual.$idx))&&*
+      }
+    }
+  }
+}
+
+tuple_matcher!(A: MA: 0, B: MB: 1);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2, D: MD: 3);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2, D: MD: 3, E: ME: 4);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2, D: MD: 3, E: ME: 4, F: MF: 5);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2, D: MD: 3, E: ME: 4, F: MF: 5, G: MG: 6);
+tuple_matcher!(A: MA: 0, B: MB: 1, C: MC: 2, D: MD: 3, E: ME: 4, F: MF: 5, G: MG: 6, H: MH: 7);
@@ -0,0 +1,86 @@
+//! Questions about a stub's return type that the macros answer where they expand, since only
+//! there is the type concrete enough to ask whether it is `()` or `Default`.
+//!
+//! `(&ReturnProbe::<T>::new()).unconfigured(..)` resolves to the impl on `ReturnProbe<T>` when one
+//! applies, and otherwise auto-refs to the catch-all impl on `&ReturnProbe<T>`. `InterceptProbe`
+//! asks the same way what an interceptor's output means for the return value.
+
+use std::marker::PhantomData;
+
+use super::ReturnValues;
+
+pub struct ReturnProbe<T>(PhantomData<T>);
+
+impl<T> ReturnProbe<T> {
+  pub fn new() -> ReturnProbe<T> { ReturnProbe(PhantomData) }
+
+  /// A probe for the values' type, for callers that can't name it.
+  pub fn of<Producer: ?Sized>(_: &ReturnValues<T, Producer>) -> ReturnProbe<T> { ReturnProbe::new() }
+}
+
+impl<T> Default for ReturnProbe<T> {
+  fn default() -> ReturnProbe<T> { ReturnProbe::new() }
+}
+
+pub trait UnitReturn {
+  fn unconfigured(&self, fn_name: &str);
+}
+
+impl UnitReturn for ReturnProbe<()> {
+  fn unconfigured(&self, _: &str) {}
+}
+
+pub trait MissingReturn<T> {
+  fn unconfigured(&self, fn_name: &str) -> T {
+    panic!("#returns was not called on [{}] prior to invocation", fn_name)
+  }
+}
+
+impl<T> MissingReturn<T> for &ReturnProbe<T> {}
+
+pub trait DefaultReturn<T> {
+  fn fallback(&self) -> Option<fn() -> T>;
+}
+
+impl<T: Default> DefaultReturn<T> for ReturnProbe<T> {
+  fn fallback(&self) -> Option<fn() -> T> { Some(T::default) }
+}
+
+pub trait NoDefaultReturn<T> {
+  fn fallback(&self) -> Option<fn() -> T> { None }
+}
+
+impl<T> NoDefaultReturn<T> for &ReturnProbe<T> {}
+
+/// Probes what an interceptor returning `Out` means for a stub returning `T`.
+pub struct InterceptProbe<Out, T>(PhantomData<(Out, T)>);
+
+impl<Out, T> InterceptProbe<Out, T> {
+  /// A probe for the interceptor's output, for callers that can't name it.
+  pub fn of(_: &Option<Out>) -> InterceptProbe<Out, T> { InterceptProbe(PhantomData) }
+}
+
+/// An interceptor returning `()` only observes the call.
+pub trait ObservingIntercept<T> {
+  fn returned(&self, out: Option<()>) -> Option<T>;
+}
+
+impl<T> ObservingIntercept<T> for InterceptProbe<(), T> {
+  fn returned(&self, _: Option<()>) -> Option<T> { None }
+}
+
+/// An interceptor returning `Option<T>` answers the call, or leaves it to the configured returns.
+pub trait FallibleIntercept<T> {
+  fn returned(&self, out: Option<Option<T>>) -> Option<T>;
+}
+
+impl<T> FallibleIntercept<T> for InterceptProbe<Option<T>, T> {
+  fn returned(&self, out: Option<Option<T>>) -> Option<T> { out.and_then(|out| out) }
+}
+
+/// An interceptor returning `T` answers the call.
+pub trait ValueIntercept<T> {
+  fn returned(&self, out: Option<T>) -> Option<T> { out }
+}
+
+impl<T> ValueIntercept<T> for &InterceptProbe<T, T> {}
@@ -0,0 +1,85 @@
+//! Failure messages for the `verify_*` methods on arg-watching stubs.
+//!
+//! Args are rendered with `{:?}` when listing calls, and with `{:#?}` when diffing the expected
+//! args against the closest call, so a struct or tuple diffs one field per line.
+
+use std::fmt::{Debug, Write};
+
+/// Describes why none of `calls` matched `expected`.
+pub fn unmatched_args<Args: Debug>(name: Option<&str>, expected: &Args, calls: &[Args]) -> String {
+  let name = name.unwrap_or("unnamed stub");
+  if calls.is_empty() {
+    return format!("Expected [{}] to be called with {:?}, but it was never called", name, expected)
+  }
+  let mut message = format!("Expected [{}] to be called with {:?}, but none of its {} calls matched.\n{}",
+                            name, expected, calls.len(), list_calls(calls));
+  if let Some((idx, diff)) = closest_call(expected, calls) {
+    let _ = write!(message, "\nClosest match is call #{} (- expected, + actual):\n{}", idx, diff);
+  }
+  message
+}
+
+/// Describes why none of `calls` satisfied a matcher.
+pub fn unmatched_calls<Args: Debug>(name: Option<&str>, calls: &[Args]) -> String {
+  let name = name.unwrap_or("unnamed stub");
+  if calls.is_empty() {
+    return format!("Expected [{}] to be called with matching args, but it was never called", name)
+  }
+  format!("Expected [{}] to be called with matching args, but none of its {} calls matched.\n{}",
+          name, calls.len(), list_calls(calls))
+}
+
+pub fn list_calls<Args: Debug>(calls: &[Args]) -> String {
+  let mut listing = String::from("Calls were:");
+  for (idx, args) in calls.iter().enumerate() {
+    let _ = write!(listing, "\n  #{} {:?}", idx, args);
+  }
+  listing
+}
+
+/// The call whose pretty-printed args differ from `expected` on the fewest lines, with its diff.
+fn closest_call<Args: Debug>(expected: &Args, calls: &[Args]) -> Option<(usize, String)> {
+  let expected = format!("{:#?}", expected);
+  calls.iter()
+    .enumerate()
+    .map(|(idx, args)| (idx, line_diff(&expected, &format!("{:#?}", args))))
+    .min_by_key(|&(_, (changed, _))| changed)
+    .map(|(idx, (_, diff))| (idx, diff))
+}
+
+/// Diffs two renderings line by line, returning the number of changed lines and the diff.
+fn line_diff(expected: &str, actual: &str) -> (usize, String) {
+  let expected = expected.lines().collect::<Vec<_>>();
+  let actual = actual.lines().collect::<Vec<_>>();
+
+  // common[i][j] is the length of the longest common subsequence of expected[i..] and actual[j..].
+  let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+  for i in (0..expected.len()).rev() {
+    for j in (0..actual.len()).rev() {
+      common[i][j] = if expected[i] == actual[j] {
+        common[i + 1][j + 1] + 1
+      } else {
+        common[i + 1][j].max(common[i][j + 1])
+      };
+    }
+  }
+
+  let (mut i, mut j, mut changed) = (0, 0, 0);
+  let mut diff = Vec::new();
+  while i < expected.len() || j < actual.len() {
+    if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+      diff.push(format!("    {}", expected[i]));
+      i += 1;
+      j += 1;
+    } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1]) {
+      diff.push(format!("  - {}", expected[i]));
+      i += 1;
+      changed += 1;
+    } else {
+      diff.push(format!("  + {}", actual[j]));
+      j += 1;
+      changed += 1;
+    }
+  }
+  (changed, diff.join("\n"))
+}
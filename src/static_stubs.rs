@@ -0,0 +1,155 @@
+//! Storage for stubs of static (receiver-less) trait methods.
+//!
+//! A static method has no `self` to hold its stub, so `instrument_stub!` keeps one stub per
+//! stubbed struct and method name in thread-local storage instead. Test harnesses usually run each
+//! test on its own thread, but `reset_static_stubs` clears the state for harnesses that don't.
+//!
+//! Expectations on a static stub are only verified when it is reset, since a panic while the
+//! thread is exiting would abort the process. Hold a `StaticStubsGuard` for the length of a test
+//! to reset, and so verify, its static stubs when the test returns.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem;
+use std::rc::Rc;
+
+use super::{Named, Stub, TakenInterceptor};
+
+struct StaticStubs {
+  stubs: HashMap<(TypeId, &'static str), Box<dyn Any>>,
+}
+
+impl Drop for StaticStubs {
+  fn drop(&mut self) {
+    // Panicking in a thread-local destructor aborts the process, so stubs still registered when
+    // the thread exits are leaked instead of having their expectations verified.
+    for (_, stub) in self.stubs.drain() {
+      mem::forget(stub);
+    }
+  }
+}
+
+thread_local! {
+  static STATIC_STUBS: RefCell<StaticStubs> = RefCell::new(StaticStubs { stubs: HashMap::new() });
+}
+
+/// Returns the stub for the static method `fn_name` on `Owner`, creating it on first use.
+///
+/// `Stub` must be the stub type the method was instrumented with, e.g.
+/// `static_stub::<TraitStub, SimpleStub<u16>>("default_port")`.
+pub fn static_stub<Owner: 'static, Stub: Default + Named + 'static>(fn_name: &'static str) -> Rc<RefCell<Stub>> {
+  STATIC_STUBS.with(|statics| {
+    statics.borrow_mut().stubs
+      .entry((TypeId::of::<Owner>(), fn_name))
+      .or_insert_with(|| {
+        let mut stub = Stub::default();
+        stub.set_name(fn_name);
+        Box::new(Rc::new(RefCell::new(stub)))
+      })
+      .downcast_ref::<Rc<RefCell<Stub>>>()
+      .unwrap_or_else(|| panic!("Static stub for [{}] was already created with a different stub type", fn_name))
+      .clone()
+  })
+}
+
+/// Resets `Owner`'s static stubs on this thread when dropped, verifying their expectations unless
+/// the test is already panicking.
+pub struct StaticStubsGuard<Owner: 'static> {
+  owner: PhantomData<fn() -> Owner>,
+}
+
+impl<Owner: 'static> StaticStubsGuard<Owner> {
+  pub fn new() -> StaticStubsGuard<Owner> {
+    StaticStubsGuard { owner: PhantomData }
+  }
+}
+
+impl<Owner: 'static> Default for StaticStubsGuard<Owner> {
+  fn default() -> StaticStubsGuard<Owner> { StaticStubsGuard::new() }
+}
+
+impl<Owner: 'static> Drop for StaticStubsGuard<Owner> {
+  fn drop(&mut self) {
+    reset_static_stubs::<Owner>();
+  }
+}
+
+/// Drops every static stub registered for `Owner` on this thread, verifying their expectations.
+pub fn reset_static_stubs<Owner: 'static>() {
+  let owner = TypeId::of::<Owner>();
+  let removed = STATIC_STUBS.with(|statics| {
+    let mut statics = statics.borrow_mut();
+    let keys = statics.stubs.keys()
+      .filter(|key| key.0 == owner)
+      .cloned()
+      .collect::<Vec<_>>();
+    keys.iter()
+      .filter_map(|key| statics.stubs.remove(key))
+      .collect::<Vec<_>>()
+  });
+  drop(removed);
+}
+
+/// Drops every static stub registered on this thread, verifying their expectations.
+pub fn reset_all_static_stubs() {
+  let removed = STATIC_STUBS.with(|statics| mem::take(&mut statics.borrow_mut().stubs));
+  drop(removed);
+}
+
+/// The calls `stub_call!` makes on a static stub, which borrow its cell only while they touch the
+/// stub. The interceptor and the `returns_with` closure run without a borrow, so they may
+/// reconfigure the stub through `static_stub`.
+#[doc(hidden)]
+pub trait StaticCall<Args, T, Interceptor: ?Sized> {
+  fn begin_call(&self, fn_name: &'static str) -> usize;
+  fn intercept<R, F: FnOnce(&mut Interceptor) -> R>(&self, call: F) -> Option<R>;
+  fn next_return(&self, fn_name: &str, args: &Args) -> Option<T>;
+  fn finish_call(&self, seq: usize, args: Args);
+  fn observe_return(&self, seq: usize, val: &T);
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> StaticCall<Args, T, Interceptor> for RefCell<Stub<Args, T, Interceptor, Recording>> {
+  fn begin_call(&self, fn_name: &'static str) -> usize {
+    self.borrow().begin_call(fn_name)
+  }
+
+  fn intercept<R, F: FnOnce(&mut Interceptor) -> R>(&self, call: F) -> Option<R> {
+    let once = self.borrow().once_interceptors.borrow_mut().pop_front();
+    if let Some(mut interceptor) = once {
+      return Some(call(&mut interceptor));
+    }
+    let mut taken = TakenInterceptor {
+      interceptor: self.borrow().call_interceptor.borrow_mut().take(),
+      // Keeps any interceptor set while this one ran.
+      put_back: |interceptor| {
+        self.borrow().call_interceptor.borrow_mut().get_or_insert(interceptor);
+      },
+    };
+    let interceptor = taken.interceptor.as_mut()?;
+    Some(call(interceptor))
+  }
+
+  fn next_return(&self, fn_name: &str, args: &Args) -> Option<T> {
+    let return_fn = {
+      let stub = self.borrow();
+      if let Some(val) = stub.priority_return(args) {
+        return Some(val)
+      }
+      match stub.return_fn {
+        Some(ref return_fn) => return_fn.clone(),
+        None => return stub.return_vals.next_value(fn_name),
+      }
+    };
+    Some(return_fn(args))
+  }
+
+  fn finish_call(&self, seq: usize, args: Args) {
+    self.borrow().finish_call(seq, args)
+  }
+
+  fn observe_return(&self, seq: usize, val: &T) {
+    self.borrow().observe_return(seq, val)
+  }
+}
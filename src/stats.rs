@@ -0,0 +1,89 @@
+//! Grouping a stub's recorded calls by their args, for tests that care how often each distinct
+//! call was made, like a cache that should only miss once per key.
+//!
+//! Args are grouped by hashing when they are `Eq + Hash`, and by comparing each call against every
+//! distinct args seen so far when they are only `PartialEq`.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::slice;
+
+/// How many calls were made with each distinct args, in order of each args' first call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArgHistogram<Args> {
+  pub entries: Vec<(Args, u32)>,
+}
+
+impl<Args: Clone + Eq + Hash> ArgHistogram<Args> {
+  pub fn by_hash(calls: &[Args]) -> ArgHistogram<Args> {
+    let mut positions = HashMap::new();
+    let mut entries = Vec::new();
+    for args in calls {
+      let pos = *positions.entry(args).or_insert_with(|| {
+        entries.push((args.clone(), 0));
+        entries.len() - 1
+      });
+      entries[pos].1 += 1;
+    }
+    ArgHistogram { entries }
+  }
+}
+
+impl<Args: Clone + PartialEq> ArgHistogram<Args> {
+  /// Groups `calls` without hashing, taking time quadratic in the number of distinct args.
+  pub fn by_eq(calls: &[Args]) -> ArgHistogram<Args> {
+    let mut entries: Vec<(Args, u32)> = Vec::new();
+    for args in calls {
+      match entries.iter_mut().find(|entry| entry.0 == *args) {
+        Some(entry) => entry.1 += 1,
+        None => entries.push((args.clone(), 1)),
+      }
+    }
+    ArgHistogram { entries }
+  }
+}
+
+impl<Args> ArgHistogram<Args> {
+  /// The number of calls made with exactly `args`.
+  pub fn count_for(&self, args: &Args) -> u32 where Args: PartialEq {
+    self.entries.iter().find(|entry| entry.0 == *args).map_or(0, |entry| entry.1)
+  }
+
+  /// Every distinct args, in order of first call.
+  pub fn distinct_args(self) -> Vec<Args> {
+    self.entries.into_iter().map(|(args, _)| args).collect()
+  }
+
+  /// The args called with more than once, with their counts.
+  pub fn repeated(&self) -> Vec<(&Args, u32)> {
+    self.iter().filter(|&(_, count)| count > 1).collect()
+  }
+
+  pub fn iter(&self) -> ArgHistogramIter<'_, Args> {
+    ArgHistogramIter { entries: self.entries.iter() }
+  }
+
+  /// The number of distinct args.
+  pub fn len(&self) -> usize { self.entries.len() }
+
+  pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+}
+
+pub struct ArgHistogramIter<'a, Args: 'a> {
+  entries: slice::Iter<'a, (Args, u32)>,
+}
+
+impl<'a, Args> Iterator for ArgHistogramIter<'a, Args> {
+  type Item = (&'a Args, u32);
+
+  fn next(&mut self) -> Option<(&'a Args, u32)> {
+    self.entries.next().map(|&(ref args, count)| (args, count))
+  }
+}
+
+impl<'a, Args> IntoIterator for &'a ArgHistogram<Args> {
+  type Item = (&'a Args, u32);
+  type IntoIter = ArgHistogramIter<'a, Args>;
+
+  fn into_iter(self) -> ArgHistogramIter<'a, Args> { self.iter() }
+}
@@ -0,0 +1,485 @@
+//! `Send + Sync` counterparts of the stubs, for traits shared across threads.
+//!
+//! `SyncStub` exposes the same fields and methods the `impl_helper!` arms rely on as `Stub`, so a
+//! struct of sync stubs is instrumented with the same `SimpleStub:`/`ArgWatchingStub:`/
+//! `InterceptingStub:`/`Stub:` syntax.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+
+use super::{history, lock, report, unmet_call_counts};
+use super::{ArgHistogram, CallCount, CallExpecter, CallJournal, CallWatcher, CallsSince, Checkpoint,
+            DeferredExpectations, Exhausted, ExpectationGroup, IgnoredArgs, Journaled, MatchingCalls, Named,
+            RecordedArgs, ReturnStubber, ReturnStubberExt, ReturnValues};
+use matchers::{self, Matcher};
+
+pub type SyncReturnFn<Args, T> = Box<dyn Fn(&Args) -> T + Send + Sync>;
+pub type SyncKeyFn<Args> = Box<dyn Fn(&Args) -> String + Send + Sync>;
+pub type SyncReturnValues<T> = ReturnValues<T, dyn Fn() -> T + Send + Sync>;
+
+/// Returns `value` for any call whose arguments satisfy `matcher`.
+pub struct SyncReturnRule<Args, T> {
+  pub matcher: Box<dyn Matcher<Args> + Send + Sync>,
+  pub value: T,
+  pub clone: fn(&T) -> T,
+}
+
+/// The `Send + Sync` counterpart of `Stub`. Its interceptor may be `FnMut`, as with `Stub`: calls
+/// from several threads take turns running it under a lock, and a call the interceptor makes back
+/// into the same stub skips interception rather than deadlocking.
+pub struct SyncStub<Args, T, Interceptor: ?Sized = dyn FnMut() + Send, Recording = RecordedArgs> {
+  pub name: Arc<Mutex<Option<&'static str>>>,
+  pub journal: Option<CallJournal>,
+  pub return_vals: SyncReturnValues<T>,
+  pub return_fn: Option<SyncReturnFn<Args, T>>,
+  pub return_rules: Vec<SyncReturnRule<Args, T>>,
+  pub call_interceptor: Mutex<Option<Box<Interceptor>>>,
+  pub intercepting_thread: Mutex<Option<ThreadId>>,
+  pub once_interceptors: Mutex<VecDeque<Box<Interceptor>>>,
+  pub call_args: Arc<Mutex<Vec<Args>>>,
+  pub call_seqs: Arc<Mutex<Vec<usize>>>,
+  pub key_fn: Option<SyncKeyFn<Args>>,
+  pub call_keys: Arc<Mutex<Vec<Option<String>>>>,
+  pub return_clone: Option<fn(&T) -> T>,
+  pub call_returns: Arc<Mutex<Vec<T>>>,
+  pub return_seqs: Arc<Mutex<Vec<usize>>>,
+  pub expected_calls: Vec<CallCount>,
+  pub expected_args: Vec<Box<dyn Matcher<Args> + Send + Sync>>,
+  pub expectation_group: Option<DeferredExpectations>,
+  pub recording: PhantomData<Recording>,
+}
+
+pub type SyncSimpleStub<T> = SyncStub<(), T, dyn FnMut() + Send, IgnoredArgs>;
+pub type SyncArgWatchingStub<T, Args> = SyncStub<Args, T>;
+pub type SyncInterceptingStub<T, Interceptor> = SyncStub<(), T, Interceptor, IgnoredArgs>;
+
+impl<Args, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  pub fn new() -> SyncStub<Args, T, Interceptor, Recording> {
+    SyncStub {
+      name: Arc::new(Mutex::new(None)),
+      journal: None,
+      return_vals: ReturnValues::new(),
+      return_fn: None,
+      return_rules: Vec::new(),
+      call_interceptor: Mutex::new(None),
+      intercepting_thread: Mutex::new(None),
+      once_interceptors: Mutex::new(VecDeque::new()),
+      call_args: Arc::new(Mutex::new(Vec::new())),
+      call_seqs: Arc::new(Mutex::new(Vec::new())),
+      key_fn: None,
+      call_keys: Arc::new(Mutex::new(Vec::new())),
+      return_clone: None,
+      call_returns: Arc::new(Mutex::new(Vec::new())),
+      return_seqs: Arc::new(Mutex::new(Vec::new())),
+      expected_calls: Vec::new(),
+      expected_args: Vec::new(),
+      expectation_group: None,
+      recording: PhantomData,
+    }
+  }
+
+  pub fn named(fn_name: &'static str) -> SyncStub<Args, T, Interceptor, Recording> {
+    let stub = SyncStub::new();
+    *lock(&stub.name) = Some(fn_name);
+    stub
+  }
+
+  pub fn record_call(&self, fn_name: &'static str, args: Args) {
+    let seq = self.begin_call(fn_name);
+    self.finish_call(seq, args);
+  }
+
+  /// Names the stub and journals a call as it starts, like `Stub#begin_call`.
+  pub fn begin_call(&self, fn_name: &'static str) -> usize {
+    *lock(&self.name) = Some(fn_name);
+    if let Some(ref journal) = self.journal {
+      journal.record(fn_name);
+    }
+    history::next_seq()
+  }
+
+  /// Records the args of the call numbered `seq` in the order calls began, like `Stub#finish_call`.
+  pub fn finish_call(&self, seq: usize, args: Args) {
+    // The args lock is taken first and held throughout, so concurrent calls see each other's
+    // insertions.
+    let mut call_args = lock(&self.call_args);
+    let pos = history::insert_seq(&mut lock(&self.call_seqs), seq);
+    lock(&self.call_keys).insert(pos, self.key_fn.as_ref().map(|key_fn| key_fn(&args)));
+    call_args.insert(pos, args);
+  }
+
+  /// Forgets every recorded call, like `Stub#reset_calls`.
+  pub fn reset_calls(&self) {
+    let mut call_args = lock(&self.call_args);
+    call_args.clear();
+    lock(&self.call_seqs).clear();
+    lock(&self.call_keys).clear();
+    lock(&self.call_returns).clear();
+    lock(&self.return_seqs).clear();
+  }
+
+  /// Puts the stub back the way `new` made it, like `Stub#reset_all`.
+  pub fn reset_all(&mut self) {
+    self.reset_calls();
+    self.return_vals = ReturnValues::new();
+    self.return_fn = None;
+    self.return_rules.clear();
+    *lock(&self.call_interceptor) = None;
+    lock(&self.once_interceptors).clear();
+    self.key_fn = None;
+    self.return_clone = None;
+    self.expected_calls.clear();
+    self.expected_args.clear();
+  }
+
+  /// Leaves the stub's unmet expectations for `group` to report, like
+  /// `Stub#report_expectations_to`.
+  pub fn report_expectations_to(&mut self, group: &ExpectationGroup) {
+    self.expectation_group = Some(group.deferred());
+  }
+
+  pub fn checkpoint(&self) -> Checkpoint {
+    Checkpoint::now()
+  }
+
+  /// Views the calls made after `checkpoint`, like `Stub#since`. Calling the stub while the view
+  /// is held deadlocks.
+  pub fn since(&self, checkpoint: Checkpoint) -> CallsSince<MutexGuard<'_, Vec<Args>>> {
+    let calls = lock(&self.call_args);
+    CallsSince {
+      name: *lock(&self.name),
+      first: checkpoint.first_call_in(&lock(&self.call_seqs)),
+      calls,
+    }
+  }
+
+  pub fn record_return(&self, val: T) {
+    self.insert_return(history::next_seq(), val);
+  }
+
+  fn insert_return(&self, seq: usize, val: T) {
+    let mut call_returns = lock(&self.call_returns);
+    let pos = history::insert_seq(&mut lock(&self.return_seqs), seq);
+    call_returns.insert(pos, val);
+  }
+
+  pub fn record_returns(&mut self) where T: Clone {
+    self.return_clone = Some(T::clone);
+  }
+
+  pub fn observe_return(&self, seq: usize, val: &T) {
+    if let Some(clone) = self.return_clone {
+      self.insert_return(seq, clone(val));
+    }
+  }
+
+  /// Returns a stub sharing this stub's call history, like `Stub#handle`.
+  pub fn handle(&self) -> SyncStub<Args, T, Interceptor, Recording> {
+    SyncStub {
+      name: self.name.clone(),
+      journal: None,
+      return_vals: ReturnValues::new(),
+      return_fn: None,
+      return_rules: Vec::new(),
+      call_interceptor: Mutex::new(None),
+      intercepting_thread: Mutex::new(None),
+      once_interceptors: Mutex::new(VecDeque::new()),
+      call_args: self.call_args.clone(),
+      call_seqs: self.call_seqs.clone(),
+      key_fn: None,
+      call_keys: self.call_keys.clone(),
+      return_clone: None,
+      call_returns: self.call_returns.clone(),
+      return_seqs: self.return_seqs.clone(),
+      expected_calls: Vec::new(),
+      expected_args: Vec::new(),
+      expectation_group: None,
+      recording: PhantomData,
+    }
+  }
+
+  pub fn set_interceptor(&mut self, f: Box<Interceptor>) {
+    *lock(&self.call_interceptor) = Some(f)
+  }
+
+  /// Queues `f` to intercept exactly one call, like `Stub#intercept_once`.
+  pub fn intercept_once(&mut self, f: Box<Interceptor>) {
+    lock(&self.once_interceptors).push_back(f);
+  }
+
+  /// Runs `call` on the next queued interceptor, or else on the interceptor set with
+  /// `set_interceptor` while holding its lock, and returns its result. Returns `None` without
+  /// waiting when the interceptor is already running on this thread, i.e. for a reentrant call.
+  /// The interceptor never leaves the lock, so it stays in place if it panics.
+  pub fn intercept<R, F: FnOnce(&mut Interceptor) -> R>(&self, call: F) -> Option<R> {
+    let once = lock(&self.once_interceptors).pop_front();
+    if let Some(mut interceptor) = once {
+      return Some(call(&mut interceptor));
+    }
+    let current = thread::current().id();
+    if *lock(&self.intercepting_thread) == Some(current) {
+      return None;
+    }
+    let mut interceptor = lock(&self.call_interceptor);
+    let interceptor = interceptor.as_mut()?;
+    *lock(&self.intercepting_thread) = Some(current);
+    let _running = InterceptingThread(&self.intercepting_thread);
+    Some(call(interceptor))
+  }
+
+  pub fn returns_from<F: Fn() -> T + Send + Sync + 'static>(&mut self, f: F) {
+    self.return_fn = None;
+    self.return_vals.set_producer(Box::new(f));
+  }
+
+  pub fn next_return(&self, fn_name: &str, args: &Args) -> Option<T> {
+    let rule = self.return_rules.iter().rev().find(|rule| rule.matcher.matches(args));
+    if let Some(rule) = rule {
+      return Some((rule.clone)(&rule.value))
+    }
+    if let Some(val) = self.return_vals.next_once() {
+      return Some(val)
+    }
+    match self.return_fn {
+      Some(ref f) => Some(f(args)),
+      None => self.return_vals.next_value(fn_name)
+    }
+  }
+
+  pub fn expect_matching<M: Matcher<Args> + Send + Sync + 'static>(&mut self, matcher: M) {
+    self.expected_args.push(Box::new(matcher));
+  }
+
+  pub fn was_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    lock(&self.call_args)
+      .iter()
+      .any(|call_args| matcher.matches(call_args))
+  }
+
+  pub fn always_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    lock(&self.call_args)
+      .iter()
+      .all(|call_args| matcher.matches(call_args))
+  }
+
+  pub fn never_called_matching<M: Matcher<Args>>(&self, matcher: M) -> bool {
+    !self.was_called_matching(matcher)
+  }
+
+  /// Counts only the calls whose args satisfy `matcher`.
+  pub fn calls_matching<M: Matcher<Args>>(&self, matcher: M) -> MatchingCalls {
+    let call_args = lock(&self.call_args);
+    MatchingCalls::count(*lock(&self.name), &call_args, matcher)
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized> SyncStub<Args, T, Interceptor> {
+  /// Computes the return value from the recorded call arguments, like `Stub#returns_with`.
+  pub fn returns_with<F: Fn(&Args) -> T + Send + Sync + 'static>(&mut self, f: F) {
+    self.return_vals.clear();
+    self.return_fn = Some(Box::new(f));
+  }
+
+  /// Returns `val` whenever the call arguments satisfy `matcher`, with the same precedence as
+  /// `Stub#when`.
+  pub fn when<F: Fn(&Args) -> bool + Send + Sync + 'static>(&mut self, matcher: F, val: T) where T: Clone {
+    self.when_matching(matchers::predicate(matcher), val)
+  }
+
+  pub fn when_matching<M: Matcher<Args> + Send + Sync + 'static>(&mut self, matcher: M, val: T) where T: Clone {
+    self.return_rules.push(SyncReturnRule { matcher: Box::new(matcher), value: val, clone: T::clone });
+  }
+
+  pub fn record_keys<F: Fn(&Args) -> String + Send + Sync + 'static>(&mut self, key_fn: F) {
+    self.key_fn = Some(Box::new(key_fn));
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Default for SyncStub<Args, T, Interceptor, Recording> {
+  fn default() -> SyncStub<Args, T, Interceptor, Recording> { SyncStub::new() }
+}
+
+impl<Args, T: Clone, Interceptor: ?Sized, Recording> ReturnStubber<T> for SyncStub<Args, T, Interceptor, Recording> {
+  fn returns(&mut self, val: T) { self.returns_in_order(vec![val]); }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> ReturnStubberExt<T> for SyncStub<Args, T, Interceptor, Recording> {
+  fn returns_once(&mut self, val: T) { self.return_vals.push_once(val); }
+  fn returns_in_order(&mut self, vals: Vec<T>) where T: Clone {
+    self.return_fn = None;
+    self.return_vals.set(vals);
+  }
+  fn when_exhausted(&mut self, behavior: Exhausted) { self.return_vals.exhausted = behavior; }
+  fn lenient(&mut self) where T: Default { self.return_vals.fallback = Some(T::default); }
+  fn strict(&mut self) { self.return_vals.fallback = None; }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> CallWatcher for SyncStub<Args, T, Interceptor, Recording> {
+  fn call_count(&self) -> u32 { lock(&self.call_args).len() as u32 }
+  fn stub_name(&self) -> Option<&'static str> { *lock(&self.name) }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> CallExpecter for SyncStub<Args, T, Interceptor, Recording> {
+  fn expect(&mut self, count: CallCount) { self.expected_calls.push(count); }
+  fn unmet_expectations(&self) -> Vec<String> {
+    let name = *lock(&self.name);
+    let mut unmet = unmet_call_counts(name, &self.expected_calls, self.call_count());
+    let call_args = lock(&self.call_args);
+    for matcher in &self.expected_args {
+      if !call_args.iter().any(|args| matcher.matches(args)) {
+        unmet.push(format!("Expected [{}] to be called with matching args, but none of its {} calls matched",
+                           name.unwrap_or("unnamed stub"), call_args.len()));
+      }
+    }
+    unmet
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Journaled for SyncStub<Args, T, Interceptor, Recording> {
+  fn report_to(&mut self, journal: &CallJournal) { self.journal = Some(journal.clone()); }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Named for SyncStub<Args, T, Interceptor, Recording> {
+  fn set_name(&mut self, fn_name: &'static str) { *lock(&self.name) = Some(fn_name); }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> Drop for SyncStub<Args, T, Interceptor, Recording> {
+  fn drop(&mut self) {
+    if let Some(ref group) = self.expectation_group {
+      if group.defer(self.unmet_expectations()).is_ok() { return }
+    }
+    if !thread::panicking() { self.verify() }
+  }
+}
+
+impl<Args: Clone, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  pub fn get_args_for_call(&self, call: usize) -> Option<Args> {
+    lock(&self.call_args)
+      .get(call)
+      .cloned()
+  }
+}
+
+impl<Args, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  /// Locks the args of every call so far, like `Stub#recorded_args`. Calling the stub
+  /// while the guard is held deadlocks.
+  pub fn recorded_args(&self) -> MutexGuard<'_, Vec<Args>> {
+    lock(&self.call_args)
+  }
+
+  pub fn with_args_for_call<R, F: FnOnce(&Args) -> R>(&self, call: usize, f: F) -> Option<R> {
+    lock(&self.call_args).get(call).map(f)
+  }
+
+  pub fn recorded_returns(&self) -> MutexGuard<'_, Vec<T>> {
+    lock(&self.call_returns)
+  }
+
+  pub fn return_for_call(&self, call: usize) -> Option<T> where T: Clone {
+    lock(&self.call_returns).get(call).cloned()
+  }
+
+  pub fn key_for_call(&self, call: usize) -> Option<String> {
+    lock(&self.call_keys).get(call).and_then(|key| key.clone())
+  }
+
+  pub fn was_called_with_key(&self, key: &str) -> bool {
+    lock(&self.call_keys).iter().any(|call_key| call_key.as_ref().is_some_and(|call_key| call_key == key))
+  }
+}
+
+impl<Args: fmt::Debug, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  #[track_caller]
+  pub fn verify_called_matching<M: Matcher<Args>>(&self, matcher: M) {
+    let call_args = lock(&self.call_args);
+    if !call_args.iter().any(|args| matcher.matches(args)) {
+      let message = report::unmatched_calls(*lock(&self.name), &call_args);
+      drop(call_args);
+      panic!("{}", message)
+    }
+  }
+}
+
+impl<Args: PartialEq + fmt::Debug, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  #[track_caller]
+  pub fn verify_called_with(&self, args: &Args) {
+    let call_args = lock(&self.call_args);
+    if !call_args.iter().any(|call_args| call_args == args) {
+      let message = report::unmatched_args(*lock(&self.name), args, &call_args);
+      drop(call_args);
+      panic!("{}", message)
+    }
+  }
+}
+
+impl<Args: fmt::Debug + 'static, T, Interceptor: ?Sized> SyncStub<Args, T, Interceptor> {
+  pub fn record_debug(&mut self) {
+    self.record_keys(|args| format!("{:?}", args))
+  }
+}
+
+impl<Args: PartialEq + Send + Sync + 'static, T, Interceptor: ?Sized> SyncStub<Args, T, Interceptor> {
+  pub fn when_args(&mut self, args: Args, val: T) where T: Clone {
+    self.when_matching(matchers::eq(args), val)
+  }
+}
+
+impl<Args: PartialEq + Send + Sync + 'static, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  pub fn expect_args(&mut self, args: Args) {
+    self.expect_matching(matchers::eq(args))
+  }
+}
+
+impl<Args: Clone + Eq + Hash, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  pub fn call_histogram(&self) -> ArgHistogram<Args> {
+    ArgHistogram::by_hash(&lock(&self.call_args))
+  }
+
+  pub fn distinct_args(&self) -> Vec<Args> {
+    self.call_histogram().distinct_args()
+  }
+}
+
+impl<Args: PartialEq, T, Interceptor: ?Sized, Recording> SyncStub<Args, T, Interceptor, Recording> {
+  /// Counts only the calls with exactly `args`.
+  pub fn calls_with_args(&self, args: &Args) -> MatchingCalls {
+    self.calls_matching(matchers::predicate(|call_args: &Args| call_args == args))
+  }
+
+  pub fn call_count_for(&self, args: &Args) -> u32 {
+    self.calls_with_args(args).count
+  }
+
+  pub fn call_histogram_by_eq(&self) -> ArgHistogram<Args> where Args: Clone {
+    ArgHistogram::by_eq(&lock(&self.call_args))
+  }
+
+  pub fn was_called_with_args(&self, args: &Args) -> bool {
+    lock(&self.call_args)
+      .iter()
+      .any(|call_args| call_args == args)
+  }
+
+  pub fn always_called_with_args(&self, args: &Args) -> bool {
+    lock(&self.call_args)
+      .iter()
+      .all(|call_args| call_args == args)
+  }
+
+  pub fn never_called_with_args(&self, args: &Args) -> bool {
+    !self.was_called_with_args(args)
+  }
+}
+
+/// Clears a stub's intercepting thread once its interceptor returns or panics.
+struct InterceptingThread<'a>(&'a Mutex<Option<ThreadId>>);
+
+impl<'a> Drop for InterceptingThread<'a> {
+  fn drop(&mut self) {
+    *lock(self.0) = None;
+  }
+}
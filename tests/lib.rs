@@ -1,4 +1,6 @@
-#![cfg_attr(feature = "nightly", feature(type_macros))]
+// The original tests predate these lints and are kept as written.
+#![allow(anonymous_parameters, bare_trait_objects, dead_code, unused_parens)]
+#![allow(clippy::borrowed_box, clippy::let_unit_value, clippy::type_complexity)]
 
 #[macro_use]
 extern crate rust_stub;
@@ -16,6 +18,7 @@ mod no_stub {
     fn self_fn(&self);
     fn mut_self_fn(&mut self);
     fn own_self_fn(self);
+    fn own_mut_self_fn(self);
     fn self_fn_args(&self, i32, &i32);
   }
 
@@ -31,12 +34,13 @@ mod no_stub {
       {nostub: self_fn (&self) -> ()}
       {nostub: mut_self_fn (&mut self) -> ()}
       {nostub: own_self_fn (self) -> ()}
+      {nostub: own_mut_self_fn (mut self) -> ()}
       {nostub: self_fn_args (&self, a: i32, b: &i32) -> ()}
     }
   }
 
   #[test]
-  #[should_panic(expected = "Method [no_self_fn] was not stubbed and static methods cannot currently be stubbed")]
+  #[should_panic(expected = "Method [no_self_fn] was not stubbed")]
   fn panics_when_not_stubbed_no_self() {
     let _ = TraitStub::no_self_fn();
   }
@@ -54,11 +58,17 @@ mod no_stub {
   }
 
   #[test]
-  #[should_panic(expected = "Method [own_self_fn] was not stubbed and self-consuming methods cannot currently be stubbed")]
+  #[should_panic(expected = "Method [own_self_fn] was not stubbed")]
   fn panics_when_not_stubbed_consuming_self_fn() {
     TraitStub::new().own_self_fn()
   }
 
+  #[test]
+  #[should_panic(expected = "Method [own_mut_self_fn] was not stubbed")]
+  fn panics_when_not_stubbed_consuming_mut_self_fn() {
+    TraitStub::new().own_mut_self_fn()
+  }
+
   #[test]
   #[should_panic(expected = "Method [self_fn_args] was not stubbed")]
   fn panics_when_not_stubbed_self_fn_args() {
@@ -100,9 +110,16 @@ mod simple_stub {
   }
 
   #[test]
-  #[should_panic(expected = "#returns was not called on [self_fn] prior to invocation")]
+  #[should_panic(expected = "#returns was not called on [self_fn_args_return] prior to invocation")]
   fn panics_when_return_not_called_earlier() {
-    TraitStub::new().self_fn()
+    TraitStub::new().self_fn_args_return(1, &1);
+  }
+
+  #[test]
+  fn returns_unit_without_being_told_to() {
+    let stub = TraitStub::new();
+    stub.self_fn();
+    assert!(stub.self_fn.was_called_once());
   }
 
   #[test]
@@ -116,7 +133,6 @@ mod simple_stub {
   #[test]
   fn records_calls_on_stubs() {
     let mut stub = TraitStub::new();
-    stub.self_fn.returns(()); // TODO: Make this unnecessary, I know this is silly
     stub.mut_self_fn.returns(());
     stub.self_fn();
     stub.mut_self_fn();
@@ -174,9 +190,16 @@ mod arg_watching_stub {
   }
 
   #[test]
-  #[should_panic(expected = "#returns was not called on [self_fn] prior to invocation")]
+  #[should_panic(expected = "#returns was not called on [self_fn_args_return] prior to invocation")]
   fn panics_when_return_not_called_earlier() {
-    TraitStub::new().self_fn()
+    TraitStub::new().self_fn_args_return(1);
+  }
+
+  #[test]
+  fn returns_unit_without_being_told_to() {
+    let stub = TraitStub::new();
+    stub.self_fn();
+    assert!(stub.self_fn.was_called_once());
   }
 
   #[test]
@@ -190,7 +213,6 @@ mod arg_watching_stub {
   #[test]
   fn records_calls_on_stubs() {
     let mut stub = TraitStub::new();
-    stub.self_fn.returns(()); // TODO: Make this unnecessary, I know this is silly
     stub.mut_self_fn.returns(());
     stub.self_fn();
     stub.mut_self_fn();
@@ -260,9 +282,16 @@ mod intercepting_stub {
   }
 
   #[test]
-  #[should_panic(expected = "#returns was not called on [self_fn] prior to invocation")]
+  #[should_panic(expected = "#returns was not called on [self_fn_args_return] prior to invocation")]
   fn panics_when_return_not_called_earlier() {
-    TraitStub::new().self_fn()
+    TraitStub::new().self_fn_args_return(1, &1);
+  }
+
+  #[test]
+  fn returns_unit_without_being_told_to() {
+    let stub = TraitStub::new();
+    stub.self_fn();
+    assert!(stub.self_fn.was_called_once());
   }
 
   #[test]
@@ -276,7 +305,6 @@ mod intercepting_stub {
   #[test]
   fn records_calls_on_stubs() {
     let mut stub = TraitStub::new();
-    stub.self_fn.returns(()); // TODO: Make this unnecessary, I know this is silly
     stub.mut_self_fn.returns(());
     stub.self_fn();
     stub.mut_self_fn();
@@ -430,7 +458,6 @@ mod multiple_trait_stub {
 
 }
 
-#[cfg(feature = "nightly")]
 mod stub_create_macro {
   use rust_stub::*;
 
@@ -528,3 +555,2573 @@ mod stub_create_macro {
     assert!(!stub.arg_watching_stub.never_called_with_args(&(1,2,3)));
   }
 }
+
+mod computed_returns {
+  use rust_stub::*;
+  use std::collections::HashMap;
+
+  trait Trait {
+    fn echo(&self, _: String) -> String;
+    fn lookup(&mut self, _: u32, _: bool) -> Option<&'static str>;
+    fn length(&self, _: &str) -> usize;
+  }
+
+  struct TraitStub {
+    echo: ArgWatchingStub<String, String>,
+    lookup: ArgWatchingStub<Option<&'static str>, (u32, bool)>,
+    length: InterceptingStub<usize, dyn FnMut(&str) -> usize>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      TraitStub {
+        echo: ArgWatchingStub::new(),
+        lookup: ArgWatchingStub::new(),
+        length: InterceptingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: echo (&self, a: String) -> String}
+      {ArgWatchingStub: lookup (&mut self, a: u32, b: bool) -> Option<&'static str>}
+      {InterceptingStub: length (&self, a: &str) -> usize}
+    }
+  }
+
+  #[test]
+  fn computes_the_return_value_from_the_args() {
+    let mut stub = TraitStub::new();
+    stub.echo.returns_with(|s| s.to_uppercase());
+    assert!(stub.echo("hello".to_owned()) == "HELLO");
+    assert!(stub.echo("world".to_owned()) == "WORLD");
+    assert!(stub.echo.was_called_n_times(2));
+    assert!(stub.echo.was_called_with_args(&"hello".to_owned()));
+  }
+
+  #[test]
+  fn can_look_up_by_the_first_arg() {
+    let mut stub = TraitStub::new();
+    let mut table = HashMap::new();
+    table.insert(1, "one");
+    table.insert(2, "two");
+    stub.lookup.returns_with(move |&(key, _)| table.get(&key).cloned());
+    assert!(stub.lookup(1, true) == Some("one"));
+    assert!(stub.lookup(2, false) == Some("two"));
+    assert!(stub.lookup(3, true).is_none());
+  }
+
+  #[test]
+  fn intercepting_stubs_compute_the_return_value_with_their_interceptor() {
+    let mut stub = TraitStub::new();
+    stub.length.set_interceptor(Box::new(|s| s.len()));
+    assert!(stub.length("hello") == 5);
+    assert!(stub.length("hi") == 2);
+  }
+
+  #[test]
+  fn the_most_recent_return_configuration_wins() {
+    let mut stub = TraitStub::new();
+    stub.echo.returns("fixed".to_owned());
+    stub.echo.returns_with(|s| s.clone());
+    assert!(stub.echo("computed".to_owned()) == "computed");
+    stub.echo.returns("fixed".to_owned());
+    assert!(stub.echo("computed".to_owned()) == "fixed");
+  }
+}
+
+mod conditional_returns {
+  use rust_stub::*;
+
+  trait Trait {
+    fn score(&self, _: u32, _: String) -> u32;
+  }
+
+  struct TraitStub {
+    score: ArgWatchingStub<u32, (u32, String)>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      TraitStub { score: ArgWatchingStub::new() }
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: score (&self, a: u32, b: String) -> u32}
+    }
+  }
+
+  #[test]
+  fn returns_the_value_for_the_matching_rule() {
+    let mut stub = TraitStub::new();
+    stub.score.returns(0);
+    stub.score.when_args((1, "a".to_owned()), 10);
+    stub.score.when(|&(a, _)| a == 2, 20);
+    assert!(stub.score(1, "a".to_owned()) == 10);
+    assert!(stub.score(1, "b".to_owned()) == 0);
+    assert!(stub.score(2, "anything".to_owned()) == 20);
+    assert!(stub.score(3, "a".to_owned()) == 0);
+    assert!(stub.score.was_called_n_times(4));
+  }
+
+  #[test]
+  fn later_rules_take_precedence() {
+    let mut stub = TraitStub::new();
+    stub.score.when(|&(a, _)| a > 5, 1);
+    stub.score.when_args((7, "x".to_owned()), 2);
+    stub.score.when(|args| args.1 == "x", 3);
+    assert!(stub.score(6, "y".to_owned()) == 1);
+    assert!(stub.score(7, "x".to_owned()) == 3);
+    assert!(stub.score(1, "x".to_owned()) == 3);
+  }
+
+  #[test]
+  fn falls_back_to_the_computed_value() {
+    let mut stub = TraitStub::new();
+    stub.score.returns_with(|&(a, _)| a * 100);
+    stub.score.when_args((1, "a".to_owned()), 10);
+    assert!(stub.score(1, "a".to_owned()) == 10);
+    assert!(stub.score(2, "a".to_owned()) == 200);
+  }
+
+  #[test]
+  fn matching_rules_take_priority_over_queued_values() {
+    let mut stub = TraitStub::new();
+    stub.score.returns(0);
+    stub.score.when_args((1, "a".to_owned()), 10);
+    stub.score.returns_once(5);
+    assert!(stub.score(1, "a".to_owned()) == 10);
+    assert!(stub.score(2, "a".to_owned()) == 5);
+    assert!(stub.score(1, "a".to_owned()) == 10);
+    assert!(stub.score(2, "a".to_owned()) == 0);
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [score] prior to invocation")]
+  fn panics_when_no_rule_matches_and_there_is_no_fallback() {
+    let mut stub = TraitStub::new();
+    stub.score.when_args((1, "a".to_owned()), 10);
+    let _ = stub.score(2, "a".to_owned());
+  }
+
+  trait Ranking {
+    fn rank(&self, _: &str) -> u32;
+  }
+
+  create_stub! {
+    RankingStub as Ranking {
+      {Stub: rank (&self, name: &str => String) -> u32}
+    }
+  }
+
+  struct LabelStub {
+    rank: InterceptingStub<u32, dyn FnMut(&str) -> Option<u32>>,
+  }
+
+  instrument_stub! {
+    LabelStub as Ranking {
+      {InterceptingStub: rank (&self, name: &str) -> u32}
+    }
+  }
+
+  #[test]
+  fn matches_borrowed_args_in_their_recorded_form() {
+    let mut stub = RankingStub::new();
+    stub.rank.returns(0);
+    stub.rank.when_args("a".to_owned(), 10);
+    assert!(stub.rank("a") == 10);
+    assert!(stub.rank("b") == 0);
+  }
+
+  #[test]
+  fn intercepting_stubs_match_args_with_a_falling_through_interceptor() {
+    let mut stub = LabelStub { rank: InterceptingStub::new() };
+    stub.rank.returns(0);
+    stub.rank.set_interceptor(Box::new(|name| if name == "a" { Some(10) } else { None }));
+    assert!(stub.rank("a") == 10);
+    assert!(stub.rank("b") == 0);
+  }
+}
+
+mod argument_matchers {
+  use rust_stub::*;
+  use rust_stub::matchers::*;
+
+  trait Trait {
+    fn record(&self, _: u32, _: f64, _: String) -> u32;
+    fn tags(&self, _: Vec<&'static str>) -> u32;
+  }
+
+  struct TraitStub {
+    record: ArgWatchingStub<u32, (u32, f64, String)>,
+    tags: ArgWatchingStub<u32, Vec<&'static str>>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      TraitStub {
+        record: ArgWatchingStub::new(),
+        tags: ArgWatchingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: record (&self, a: u32, b: f64, c: String) -> u32}
+      {ArgWatchingStub: tags (&self, a: Vec<&'static str>) -> u32}
+    }
+  }
+
+  #[test]
+  fn matches_calls_with_tuple_matchers() {
+    let mut stub = TraitStub::new();
+    stub.record.returns(0);
+    let _ = stub.record(1, 0.1 + 0.2, "id-1234".to_owned());
+    assert!(stub.record.was_called_matching((eq(1), approx(0.3, 1e-9), contains("1234"))));
+    assert!(stub.record.was_called_matching((any(), any(), any())));
+    assert!(!stub.record.was_called_matching((eq(2), any(), any())));
+    assert!(stub.record.never_called_matching((ne(1), any(), any())));
+  }
+
+  #[test]
+  fn supports_comparisons_and_ranges() {
+    let mut stub = TraitStub::new();
+    stub.record.returns(0);
+    let _ = stub.record(5, 1.0, String::new());
+    let _ = stub.record(7, 2.0, String::new());
+    assert!(stub.record.always_called_matching((in_range(5..=7), gt(0.5), any())));
+    assert!(stub.record.always_called_matching((ge(5), le(2.0), any())));
+    assert!(!stub.record.always_called_matching((lt(6), any(), any())));
+    assert!(!stub.record.was_called_matching((in_range(8..), any(), any())));
+  }
+
+  #[test]
+  fn supports_predicates_and_combinators() {
+    let mut stub = TraitStub::new();
+    stub.record.returns(0);
+    let _ = stub.record(4, 1.0, "hello".to_owned());
+    let small = predicate(|x: &u32| *x < 10);
+    assert!(stub.record.was_called_matching((and(small, gt(3)), any(), not(contains('z')))));
+    assert!(stub.record.was_called_matching((or(eq(1), eq(4)), any(), any())));
+    assert!(!stub.record.was_called_matching((not(any()), any(), any())));
+  }
+
+  #[test]
+  fn matches_single_args_and_collections() {
+    let mut stub = TraitStub::new();
+    stub.tags.returns(0);
+    let _ = stub.tags(vec!["a", "b"]);
+    assert!(stub.tags.was_called_matching(contains("b")));
+    assert!(!stub.tags.was_called_matching(contains("c")));
+  }
+
+  #[test]
+  fn matchers_select_conditional_returns() {
+    let mut stub = TraitStub::new();
+    stub.record.returns(0);
+    stub.record.when_matching((eq(1), any(), any()), 10);
+    stub.record.when_matching((any(), approx(2.0, 0.01), any()), 20);
+    assert!(stub.record(1, 0.0, String::new()) == 10);
+    assert!(stub.record(1, 2.001, String::new()) == 20);
+    assert!(stub.record(2, 0.0, String::new()) == 0);
+  }
+
+  #[test]
+  fn leaves_prelude_traits_usable_after_a_glob_import() {
+    fn same<T: Eq>(a: T, b: T) -> bool { a == b }
+    assert!(same(1, 1));
+    assert!(eq(1).matches(&1));
+  }
+}
+
+mod expectations {
+  use rust_stub::*;
+  use rust_stub::matchers::*;
+
+  trait Trait {
+    fn simple_stub(&self) -> u32;
+    fn arg_watching_stub(&self, _: u32, _: String) -> u32;
+    fn intercepting_stub(&self, _: &u32) -> u32;
+  }
+
+  struct TraitStub {
+    simple_stub: SimpleStub<u32>,
+    arg_watching_stub: ArgWatchingStub<u32, (u32, String)>,
+    intercepting_stub: InterceptingStub<u32, dyn Fn(&u32)>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      let mut stub = TraitStub {
+        simple_stub: SimpleStub::new(),
+        arg_watching_stub: ArgWatchingStub::new(),
+        intercepting_stub: InterceptingStub::new(),
+      };
+      stub.simple_stub.returns(1);
+      stub.arg_watching_stub.returns(2);
+      stub.intercepting_stub.returns(3);
+      stub
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {SimpleStub: simple_stub (&self) -> u32}
+      {ArgWatchingStub: arg_watching_stub (&self, a: u32, b: String) -> u32}
+      {InterceptingStub: intercepting_stub (&self, a: &u32) -> u32}
+    }
+  }
+
+  #[test]
+  fn met_expectations_do_not_panic_on_drop() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.expect_calls(2);
+    stub.arg_watching_stub.expect_at_least(1);
+    stub.arg_watching_stub.expect_args((1, "a".to_owned()));
+    stub.arg_watching_stub.expect_matching((gt(1), any()));
+    stub.intercepting_stub.expect_never();
+    let _ = stub.simple_stub();
+    let _ = stub.simple_stub();
+    let _ = stub.arg_watching_stub(1, "a".to_owned());
+    let _ = stub.arg_watching_stub(2, "b".to_owned());
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [simple_stub] to be called exactly 2 times, but it was called 1 times")]
+  fn panics_on_drop_when_the_call_count_is_unmet() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.expect_calls(2);
+    let _ = stub.simple_stub();
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [intercepting_stub] to be called exactly 0 times, but it was called 1 times")]
+  fn panics_on_drop_when_never_is_unmet() {
+    let mut stub = TraitStub::new();
+    stub.intercepting_stub.expect_never();
+    let _ = stub.intercepting_stub(&1);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [arg_watching_stub] to be called with matching args, but none of its 1 calls matched")]
+  fn panics_on_drop_when_the_args_are_unmet() {
+    let mut stub = TraitStub::new();
+    stub.arg_watching_stub.expect_args((1, "a".to_owned()));
+    let _ = stub.arg_watching_stub(1, "b".to_owned());
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [unnamed stub] to be called at least 1 times, but it was called 0 times")]
+  fn can_verify_before_drop() {
+    let mut stub = SimpleStub::<u32>::new();
+    stub.expect_at_least(1);
+    stub.verify();
+  }
+
+  trait Lifecycle {
+    fn start(&self);
+    fn stop(&self);
+  }
+
+  create_stub! {
+    LifecycleStub as Lifecycle {
+      {SimpleStub: start (&self) -> ()}
+      {SimpleStub: stop (&self) -> ()}
+    }
+  }
+
+  #[test]
+  fn names_generated_stubs_up_front() {
+    let stub = LifecycleStub::new();
+    assert!(stub.stop.stub_name() == Some("stop"));
+    stub.stop();
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [stop] to be called exactly 1 times, but it was called 0 times")]
+  fn names_generated_stubs_that_were_never_called() {
+    let mut stub = LifecycleStub::new();
+    stub.stop.expect_calls(1);
+    stub.start();
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [start] to be called exactly 1 times, but it was called 0 times\n\
+                             Expected [stop] to be called exactly 1 times, but it was called 0 times")]
+  fn reports_the_unmet_expectations_of_every_generated_stub_on_drop() {
+    let mut stub = LifecycleStub::new();
+    stub.start.expect_calls(1);
+    stub.stop.expect_calls(1);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [start] to be called exactly 0 times, but it was called 1 times\n\
+                             Expected [stop] to be called at least 1 times, but it was called 0 times")]
+  fn verifies_every_generated_stub_at_once() {
+    let mut stub = LifecycleStub::new();
+    stub.start.expect_never();
+    stub.stop.expect_at_least(1);
+    stub.start();
+    stub.verify();
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [start] to be called exactly 2 times, but it was called 1 times")]
+  fn verifies_a_stub_that_outlives_its_struct_itself() {
+    let start = {
+      let mut stub = LifecycleStub::new();
+      stub.start.expect_calls(2);
+      stub.start();
+      let LifecycleStub { start, .. } = stub;
+      start
+    };
+    assert!(start.was_called_once());
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [simple_stub] to be called at least 1 times, but it was called 0 times")]
+  fn names_stubs_built_by_hand_that_were_never_called() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub = SimpleStub::named("simple_stub");
+    stub.simple_stub.returns(1);
+    stub.simple_stub.expect_at_least(1);
+  }
+
+  #[test]
+  #[should_panic(expected = "the test failed for another reason")]
+  fn does_not_panic_again_while_unwinding() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.expect_calls(5);
+    stub.arg_watching_stub.expect_never();
+    let _ = stub.arg_watching_stub(1, "a".to_owned());
+    panic!("the test failed for another reason");
+  }
+}
+
+mod call_ordering {
+  use rust_stub::*;
+
+  trait Connection {
+    fn open(&mut self, _: String) -> bool;
+    fn write(&mut self, _: u32) -> u32;
+    fn close(&mut self);
+  }
+
+  struct ConnectionStub {
+    open: ArgWatchingStub<bool, String>,
+    write: InterceptingStub<u32, dyn Fn(u32)>,
+    close: SimpleStub<()>,
+  }
+
+  impl ConnectionStub {
+    fn new(journal: &CallJournal) -> ConnectionStub {
+      let mut stub = ConnectionStub {
+        open: ArgWatchingStub::new(),
+        write: InterceptingStub::new(),
+        close: SimpleStub::new(),
+      };
+      stub.open.returns(true);
+      stub.write.returns(0);
+      stub.close.returns(());
+      stub.open.report_to(journal);
+      stub.write.report_to(journal);
+      stub.close.report_to(journal);
+      stub
+    }
+  }
+
+  instrument_stub! {
+    ConnectionStub as Connection {
+      {ArgWatchingStub: open (&mut self, a: String) -> bool}
+      {InterceptingStub: write (&mut self, a: u32) -> u32}
+      {SimpleStub: close (&mut self) -> ()}
+    }
+  }
+
+  fn run_protocol<C: Connection>(conn: &mut C) {
+    conn.open("localhost".to_owned());
+    conn.write(1);
+    conn.write(2);
+    conn.close();
+  }
+
+  trait Logger {
+    fn log(&self, _: &str);
+  }
+
+  create_stub! {
+    LoggerStub as Logger {
+      {ArgWatchingStub: log (&self, line: &str => String) -> ()}
+    }
+  }
+
+  #[test]
+  fn journals_outer_calls_before_the_calls_they_make() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    let mut logger = LoggerStub::new();
+    logger.report_to(&journal);
+    let logger = ::std::rc::Rc::new(logger);
+    let inner = logger.clone();
+    stub.write.set_interceptor(Box::new(move |_| inner.log("writing")));
+    run_protocol(&mut stub);
+    assert!(journal.calls() == vec!["open", "write", "log", "write", "log", "close"]);
+    assert!(logger.log.was_called_with_args(&"writing".to_owned()));
+  }
+
+  trait Echo {
+    fn say(&self, _: &str) -> usize;
+  }
+
+  create_stub! {
+    EchoStub as Echo {
+      {Stub: say (&self, line: &str => String) -> usize}
+    }
+  }
+
+  #[test]
+  fn records_nested_calls_in_the_order_they_were_journaled() {
+    let journal = CallJournal::new();
+    let stub = ::std::rc::Rc::new_cyclic(|this: &::std::rc::Weak<EchoStub>| {
+      let mut stub = EchoStub::new();
+      let this = this.clone();
+      stub.report_to(&journal);
+      stub.say.record_returns();
+      stub.say.set_interceptor(Box::new(move |line| {
+        this.upgrade().map(|stub| stub.say(&line.to_uppercase()) + 1)
+      }));
+      stub.say.returns(1);
+      stub
+    });
+    assert!(stub.say("hi") == 2);
+    assert!(journal.calls() == vec!["say", "say"]);
+    assert!(*stub.say.recorded_args() == ["hi".to_owned(), "HI".to_owned()]);
+    assert!(*stub.say.recorded_returns() == [2, 1]);
+  }
+
+  #[test]
+  fn journals_calls_across_stubs_in_sequence() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    run_protocol(&mut stub);
+    assert!(journal.calls() == vec!["open", "write", "write", "close"]);
+    journal.in_order(&["open", "write", "close"]);
+    journal.in_order(&["open", "close"]);
+    journal.called_before("open", "write");
+    journal.called_before("write", "close");
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [open] to be called after [write] (call #1), but it was only called at #0. Calls were: #0 open, #1 write, #2 write, #3 close")]
+  fn in_order_names_the_offending_call() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    run_protocol(&mut stub);
+    journal.in_order(&["write", "open"]);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [close] to be called before [open], but [open] (call #0) came before [close] (call #3)")]
+  fn called_before_names_the_offending_calls() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    run_protocol(&mut stub);
+    journal.called_before("close", "open");
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [close] to be called before [open], but [close] was never called. Calls were: #0 open")]
+  fn called_before_reports_missing_calls() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    stub.open("localhost".to_owned());
+    journal.called_before("close", "open");
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [write] to be called after [open] (call #0), but it was never called")]
+  fn in_order_reports_missing_calls() {
+    let journal = CallJournal::new();
+    let mut stub = ConnectionStub::new(&journal);
+    stub.open("localhost".to_owned());
+    journal.in_order(&["open", "write"]);
+  }
+}
+
+mod sync_stubs {
+  use rust_stub::*;
+  use rust_stub::matchers::*;
+  use std::sync::Arc;
+  use std::thread;
+
+  trait Service: Send + Sync {
+    fn ping(&self) -> bool;
+    fn lookup(&self, _: u32) -> String;
+    fn notify(&self, _: &str) -> u32;
+  }
+
+  struct ServiceStub {
+    ping: SyncSimpleStub<bool>,
+    lookup: SyncArgWatchingStub<String, u32>,
+    notify: SyncInterceptingStub<u32, dyn Fn(&str) + Send + Sync>,
+  }
+
+  impl ServiceStub {
+    fn new() -> ServiceStub {
+      ServiceStub {
+        ping: SyncSimpleStub::new(),
+        lookup: SyncArgWatchingStub::new(),
+        notify: SyncInterceptingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    ServiceStub as Service {
+      {SimpleStub: ping (&self) -> bool}
+      {ArgWatchingStub: lookup (&self, a: u32) -> String}
+      {InterceptingStub: notify (&self, a: &str) -> u32}
+    }
+  }
+
+  fn hammer(service: Arc<dyn Service>) {
+    let handles: Vec<_> = (0..4).map(|i| {
+      let service = service.clone();
+      thread::spawn(move || {
+        assert!(service.ping());
+        assert!(service.lookup(i) == format!("user-{}", i));
+        service.notify("done");
+      })
+    }).collect();
+    for handle in handles {
+      handle.join().unwrap();
+    }
+  }
+
+  #[test]
+  fn can_be_shared_across_threads() {
+    let mut stub = ServiceStub::new();
+    stub.ping.returns(true);
+    stub.lookup.returns_with(|id| format!("user-{}", id));
+    stub.notify.returns(0);
+    stub.notify.set_interceptor(Box::new(|message| assert!(message == "done")));
+    let stub = Arc::new(stub);
+    hammer(stub.clone());
+    assert!(stub.ping.was_called_n_times(4));
+    assert!(stub.lookup.was_called_n_times(4));
+    assert!(stub.notify.was_called_n_times(4));
+    assert!(stub.lookup.always_called_matching(in_range(0..4)));
+    assert!(stub.lookup.was_called_with_args(&3));
+  }
+
+  #[test]
+  fn supports_rules_sequences_and_journals() {
+    let journal = CallJournal::new();
+    let mut stub = ServiceStub::new();
+    stub.ping.returns_in_order(vec![true, false]);
+    stub.lookup.returns("nobody".to_owned());
+    stub.lookup.when_args(1, "root".to_owned());
+    stub.ping.report_to(&journal);
+    stub.lookup.report_to(&journal);
+    let stub = Arc::new(stub);
+    let worker = stub.clone();
+    thread::spawn(move || {
+      assert!(worker.ping());
+      assert!(worker.lookup(1) == "root");
+      assert!(!worker.ping());
+      assert!(worker.lookup(2) == "nobody");
+    }).join().unwrap();
+    journal.in_order(&["ping", "lookup", "ping", "lookup"]);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [ping] to be called exactly 2 times, but it was called 1 times")]
+  fn verifies_expectations_on_drop() {
+    let mut stub = ServiceStub::new();
+    stub.ping.returns(true);
+    stub.ping.expect_calls(2);
+    let stub = Arc::new(stub);
+    let worker = stub.clone();
+    thread::spawn(move || { worker.ping(); }).join().unwrap();
+  }
+}
+
+mod self_consuming_stub {
+  use rust_stub::*;
+
+  trait Trait {
+    fn build(self) -> Result<u32, String>;
+    fn into_parts(self, _: &str) -> Vec<u32>;
+    fn consume(self, _: u32, _: String) -> u32;
+    fn consume_mut(self, _: u32) -> u32;
+  }
+
+  struct TraitStub {
+    build: SimpleStub<Result<u32, String>>,
+    into_parts: InterceptingStub<Vec<u32>, dyn Fn(&str)>,
+    consume: ArgWatchingStub<u32, (u32, String)>,
+    consume_mut: ArgWatchingStub<u32, u32>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      TraitStub {
+        build: SimpleStub::new(),
+        into_parts: InterceptingStub::new(),
+        consume: ArgWatchingStub::new(),
+        consume_mut: ArgWatchingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {SimpleStub: build (self) -> Result<u32, String>}
+      {InterceptingStub: into_parts (self, a: &str) -> Vec<u32>}
+      {ArgWatchingStub: consume (self, a: u32, b: String) -> u32}
+      {ArgWatchingStub: consume_mut (mut self, a: u32) -> u32}
+    }
+  }
+
+  #[test]
+  fn records_calls_through_a_handle_taken_before_the_move() {
+    let mut stub = TraitStub::new();
+    stub.build.returns(Ok(5));
+    let build = stub.build.handle();
+    assert!(!build.was_called());
+    assert!(stub.build() == Ok(5));
+    assert!(build.was_called_once());
+  }
+
+  #[test]
+  fn intercepts_self_consuming_calls() {
+    let mut stub = TraitStub::new();
+    stub.into_parts.returns(vec![1, 2]);
+    stub.into_parts.set_interceptor(Box::new(|sep| assert!(sep == ",")));
+    let into_parts = stub.into_parts.handle();
+    assert!(stub.into_parts(",") == vec![1, 2]);
+    assert!(into_parts.was_called_once());
+  }
+
+  #[test]
+  fn records_args_of_self_and_mut_self_calls() {
+    let mut stub = TraitStub::new();
+    stub.consume.returns(10);
+    let consume = stub.consume.handle();
+    assert!(stub.consume(3, "x".to_owned()) == 10);
+    assert!(consume.was_called_with_args(&(3, "x".to_owned())));
+    assert!(consume.get_args_for_call(0) == Some((3, "x".to_owned())));
+
+    let mut stub = TraitStub::new();
+    stub.consume_mut.returns(20);
+    let consume_mut = stub.consume_mut.handle();
+    assert!(stub.consume_mut(4) == 20);
+    assert!(consume_mut.was_called_with_args(&4));
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [consume_mut] to be called exactly 2 times, but it was called 1 times")]
+  fn verifies_expectations_when_consumed() {
+    let mut stub = TraitStub::new();
+    stub.consume_mut.returns(10);
+    stub.consume_mut.expect_calls(2);
+    let _ = stub.consume_mut(1);
+  }
+}
+
+mod static_stub {
+  use rust_stub::*;
+
+  trait Trait {
+    fn default_port() -> u16;
+    fn parse(_: String, _: u32) -> Result<u32, String>;
+    fn validate(_: &str) -> bool;
+  }
+
+  struct TraitStub;
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {SimpleStub: default_port () -> u16}
+      {ArgWatchingStub: parse (a: String, b: u32) -> Result<u32, String>}
+      {InterceptingStub: validate (a: &str) -> bool}
+    }
+  }
+
+  fn default_port() -> ::std::rc::Rc<::std::cell::RefCell<SimpleStub<u16>>> {
+    static_stub::<TraitStub, SimpleStub<u16>>("default_port")
+  }
+
+  #[test]
+  fn stubs_static_methods() {
+    default_port().borrow_mut().returns(8080);
+    assert!(TraitStub::default_port() == 8080);
+    assert!(default_port().borrow().was_called_once());
+  }
+
+  #[test]
+  fn records_args_of_static_methods() {
+    let parse = static_stub::<TraitStub, ArgWatchingStub<Result<u32, String>, (String, u32)>>("parse");
+    parse.borrow_mut().returns_with(|args| args.0.parse().map_err(|_| "not a number".to_owned()));
+    assert!(TraitStub::parse("12".to_owned(), 10) == Ok(12));
+    assert!(TraitStub::parse("x".to_owned(), 10).is_err());
+    assert!(parse.borrow().was_called_with_args(&("12".to_owned(), 10)));
+  }
+
+  #[test]
+  fn intercepts_static_methods() {
+    let validate = static_stub::<TraitStub, InterceptingStub<bool, dyn FnMut(&str)>>("validate");
+    validate.borrow_mut().returns(true);
+    validate.borrow_mut().set_interceptor(Box::new(|s| assert!(s == "input")));
+    assert!(TraitStub::validate("input"));
+    assert!(validate.borrow().was_called_once());
+  }
+
+  #[test]
+  fn lets_interceptors_reconfigure_static_stubs() {
+    let validate = static_stub::<TraitStub, InterceptingStub<bool, dyn FnMut(&str)>>("validate");
+    validate.borrow_mut().returns(false);
+    validate.borrow_mut().set_interceptor(Box::new(|s| {
+      static_stub::<TraitStub, InterceptingStub<bool, dyn FnMut(&str)>>("validate").borrow_mut().returns(s == "ok");
+    }));
+    assert!(TraitStub::validate("ok"));
+    assert!(!TraitStub::validate("no"));
+    assert!(validate.borrow().was_called_n_times(2));
+  }
+
+  #[test]
+  fn lets_returns_with_reconfigure_static_stubs() {
+    let parse = static_stub::<TraitStub, ArgWatchingStub<Result<u32, String>, (String, u32)>>("parse");
+    parse.borrow_mut().returns_with(|args| {
+      static_stub::<TraitStub, ArgWatchingStub<Result<u32, String>, (String, u32)>>("parse")
+        .borrow_mut().returns(Err("called twice".to_owned()));
+      Ok(args.1)
+    });
+    assert!(TraitStub::parse("a".to_owned(), 5) == Ok(5));
+    assert!(TraitStub::parse("b".to_owned(), 5) == Err("called twice".to_owned()));
+  }
+
+  #[test]
+  fn resets_static_stubs() {
+    default_port().borrow_mut().returns(1);
+    let _ = TraitStub::default_port();
+    reset_static_stubs::<TraitStub>();
+    assert!(!default_port().borrow().was_called());
+    default_port().borrow_mut().returns(2);
+    assert!(TraitStub::default_port() == 2);
+    reset_all_static_stubs();
+    assert!(!default_port().borrow().was_called());
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [default_port] to be called exactly 2 times, but it was called 1 times")]
+  fn verifies_expectations_on_reset() {
+    default_port().borrow_mut().returns(1);
+    default_port().borrow_mut().expect_calls(2);
+    let _ = TraitStub::default_port();
+    reset_static_stubs::<TraitStub>();
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [default_port] to be called exactly 2 times, but it was called 1 times")]
+  fn verifies_expectations_when_the_guard_drops() {
+    let _guard = StaticStubsGuard::<TraitStub>::new();
+    default_port().borrow_mut().returns(1);
+    default_port().borrow_mut().expect_calls(2);
+    let _ = TraitStub::default_port();
+  }
+
+  #[test]
+  fn guards_reset_met_expectations_quietly() {
+    {
+      let _guard = StaticStubsGuard::<TraitStub>::new();
+      default_port().borrow_mut().returns(1);
+      default_port().borrow_mut().expect_calls(1);
+      let _ = TraitStub::default_port();
+    }
+    assert!(!default_port().borrow().was_called());
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [default_port] to be called exactly 1 times, but it was called 0 times")]
+  fn names_static_stubs_that_were_never_called() {
+    default_port().borrow_mut().expect_calls(1);
+    reset_static_stubs::<TraitStub>();
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [default_port] prior to invocation")]
+  fn panics_when_return_not_called_earlier() {
+    let _ = TraitStub::default_port();
+  }
+}
+
+mod stubbable_attribute {
+  use rust_stub::*;
+
+  struct Handle;
+
+  #[rust_stub::stubbable]
+  trait Store {
+    fn get(&self, key: String, version: Option<u32>) -> Option<u32>;
+    fn describe(&self, key: &str) -> String;
+    fn attach(&self, handle: Handle) -> bool;
+    #[stub(SimpleStub)]
+    fn flush(&mut self) -> usize;
+    fn close(self) -> bool;
+    fn default_port() -> u16;
+    #[stub(nostub)]
+    fn name(&self) -> &str;
+    fn size(&self) -> usize { 42 }
+  }
+
+  #[test]
+  fn watches_args_that_are_clone_and_eq() {
+    let mut stub = StoreStub::new();
+    stub.get.returns(Some(3));
+    assert!(stub.get("a".to_owned(), None) == Some(3));
+    assert!(stub.get.was_called_with_args(&("a".to_owned(), None)));
+  }
+
+  #[test]
+  fn watches_borrowed_args_in_owned_form() {
+    let mut stub = StoreStub::new();
+    stub.describe.returns("described".to_owned());
+    assert!(stub.describe("a") == "described");
+    assert!(stub.describe.was_called_with_args(&"a".to_owned()));
+  }
+
+  #[test]
+  fn intercepts_other_args() {
+    let mut stub = StoreStub::new();
+    stub.attach.returns(true);
+    stub.attach.set_interceptor(Box::new(|_| ()));
+    assert!(stub.attach(Handle));
+    assert!(stub.attach.was_called_once());
+  }
+
+  #[test]
+  fn honors_stub_kind_overrides() {
+    let mut stub = StoreStub::new();
+    stub.flush.returns(2);
+    assert!(stub.flush() == 2);
+    assert!(stub.flush.was_called_once());
+  }
+
+  #[test]
+  #[should_panic(expected = "Method [name] was not stubbed")]
+  fn panics_on_nostub_methods() {
+    let stub = StoreStub::new();
+    stub.name();
+  }
+
+  #[test]
+  fn keeps_default_methods() {
+    let stub = StoreStub::new();
+    assert!(stub.size() == 42);
+  }
+
+  #[test]
+  fn stubs_self_consuming_methods() {
+    let mut stub = StoreStub::new();
+    stub.close.returns(true);
+    let close = stub.close.handle();
+    assert!(stub.close());
+    assert!(close.was_called_once());
+  }
+
+  #[test]
+  fn stubs_static_methods() {
+    let default_port = static_stub::<StoreStub, ArgWatchingStub<u16, ()>>("default_port");
+    default_port.borrow_mut().returns(8080);
+    assert!(StoreStub::default_port() == 8080);
+    assert!(default_port.borrow().was_called_once());
+  }
+}
+
+mod default_returns {
+  use rust_stub::*;
+
+  #[derive(Clone)]
+  struct Connection;
+
+  trait Trait {
+    fn count(&self) -> u32;
+    fn name(&self, _: u32) -> String;
+    fn connect(&self) -> Connection;
+    fn close(&mut self);
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {SimpleStub: count (&self) -> u32}
+      {ArgWatchingStub: name (&self, id: u32) -> String}
+      {InterceptingStub: connect (&self) -> Connection}
+      {SimpleStub: close (&mut self) -> ()}
+    }
+  }
+
+  #[test]
+  fn falls_back_to_default_on_lenient_stubs() {
+    let mut stub = TraitStub::new();
+    stub.count.lenient();
+    assert!(stub.count() == 0);
+    assert!(stub.count.was_called_once());
+  }
+
+  #[test]
+  fn prefers_configured_returns_over_the_default() {
+    let mut stub = TraitStub::new();
+    stub.name.lenient();
+    stub.name.when_args(1, "one".to_owned());
+    assert!(stub.name(1) == "one");
+    assert!(stub.name(2).is_empty());
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [count] prior to invocation")]
+  fn panics_again_once_strict() {
+    let mut stub = TraitStub::new();
+    stub.count.lenient();
+    stub.count.strict();
+    stub.count();
+  }
+
+  #[test]
+  fn makes_every_default_stub_lenient_at_once() {
+    let mut stub = TraitStub::new();
+    stub.lenient();
+    assert!(stub.count() == 0);
+    assert!(stub.name(7).is_empty());
+    stub.close();
+    assert!(stub.close.was_called_once());
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [connect] prior to invocation")]
+  fn still_panics_for_types_without_a_default() {
+    let mut stub = TraitStub::new();
+    stub.lenient();
+    stub.connect();
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [name] prior to invocation")]
+  fn makes_every_stub_strict_at_once() {
+    let mut stub = TraitStub::new();
+    stub.lenient();
+    stub.strict();
+    stub.name(1);
+  }
+}
+
+mod owned_returns {
+  use rust_stub::*;
+  use std::cell::Cell;
+  use std::error::Error;
+  use std::rc::Rc;
+
+  #[derive(Debug, PartialEq)]
+  struct Handle(u32);
+
+  trait Trait {
+    fn open(&self, _: String) -> Result<Handle, Box<dyn Error>>;
+    fn next_handle(&self) -> Handle;
+    fn fork(&self) -> Self;
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: open (&self, path: String) -> Result<Handle, Box<dyn Error>>}
+      {SimpleStub: next_handle (&self) -> Handle}
+      {SimpleStub: fork (&self) -> Self}
+    }
+  }
+
+  #[test]
+  fn hands_out_queued_values_once_each() {
+    let mut stub = TraitStub::new();
+    stub.next_handle.returns_once(Handle(1));
+    stub.next_handle.returns_once(Handle(2));
+    assert!(stub.next_handle() == Handle(1));
+    assert!(stub.next_handle() == Handle(2));
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [next_handle] prior to invocation")]
+  fn panics_once_the_queue_runs_dry() {
+    let mut stub = TraitStub::new();
+    stub.next_handle.returns_once(Handle(1));
+    stub.next_handle();
+    stub.next_handle();
+  }
+
+  #[test]
+  fn builds_values_from_a_factory() {
+    let mut stub = TraitStub::new();
+    let built = Rc::new(Cell::new(0));
+    let counter = built.clone();
+    stub.next_handle.returns_from(move || {
+      counter.set(counter.get() + 1);
+      Handle(counter.get())
+    });
+    assert!(stub.next_handle() == Handle(1));
+    assert!(stub.next_handle() == Handle(2));
+    assert!(built.get() == 2);
+  }
+
+  #[test]
+  fn serves_queued_values_before_the_factory() {
+    let mut stub = TraitStub::new();
+    stub.next_handle.returns_from(|| Handle(0));
+    stub.next_handle.returns_once(Handle(9));
+    assert!(stub.next_handle() == Handle(9));
+    assert!(stub.next_handle() == Handle(0));
+  }
+
+  #[test]
+  fn returns_errors_that_cannot_be_cloned() {
+    let mut stub = TraitStub::new();
+    stub.open.returns_once(Ok(Handle(3)));
+    stub.open.returns_from(|| Err("no such file".into()));
+    assert!(stub.open("a".to_owned()).unwrap() == Handle(3));
+    assert!(stub.open("b".to_owned()).unwrap_err().to_string() == "no such file");
+    assert!(stub.open.was_called_with_args(&"b".to_owned()));
+  }
+
+  #[test]
+  fn serves_queued_values_before_computed_ones() {
+    let mut stub = TraitStub::new();
+    stub.open.returns_with(|path| Ok(Handle(path.len() as u32)));
+    stub.open.returns_once(Err("busy".into()));
+    assert!(stub.open("abc".to_owned()).is_err());
+    assert!(stub.open("abc".to_owned()).unwrap() == Handle(3));
+  }
+
+  #[test]
+  fn returns_self() {
+    let mut stub = TraitStub::new();
+    stub.fork.returns_from(TraitStub::new);
+    let fork = stub.fork();
+    assert!(!fork.fork.was_called());
+    assert!(stub.fork.was_called_once());
+  }
+
+  struct FixedStubber(Option<u32>);
+
+  impl ReturnStubber<u32> for FixedStubber {
+    fn returns(&mut self, val: u32) { self.0 = Some(val); }
+  }
+
+  #[test]
+  fn implements_return_stubber_with_just_returns() {
+    let mut stubber = FixedStubber(None);
+    stubber.returns(3);
+    assert!(stubber.0 == Some(3));
+  }
+
+  #[test]
+  fn returns_non_clone_values_from_sync_stubs() {
+    let mut stub: SyncSimpleStub<Handle> = SyncSimpleStub::new();
+    stub.returns_from(|| Handle(4));
+    assert!(stub.return_vals.next_value("sync") == Some(Handle(4)));
+  }
+}
+
+mod owned_args {
+  use rust_stub::*;
+
+  #[derive(Clone, Debug, PartialEq)]
+  struct Config {
+    name: String,
+    retries: u32,
+  }
+
+  trait Trait {
+    fn greet(&self, _: &str) -> String;
+    fn write(&mut self, _: &[u8], _: bool) -> usize;
+    fn apply(&self, _: &Config) -> bool;
+    fn rename(&self, _: &Config, _: &str) -> bool;
+    fn parse(_: &str) -> u32;
+  }
+
+  fn config_name(config: &Config) -> String {
+    config.name.clone()
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: greet (&self, name: &str => String) -> String}
+      {ArgWatchingStub: write (&mut self, buf: &[u8] => Vec<u8>, flush: bool) -> usize}
+      {ArgWatchingStub: apply (&self, config: &Config => Config) -> bool}
+      {ArgWatchingStub: rename (&self, config: &Config => String = config_name, name: &str => String) -> bool}
+      {ArgWatchingStub: parse (input: &str => String) -> u32}
+    }
+  }
+
+  #[test]
+  fn records_borrowed_strs_as_strings() {
+    let mut stub = TraitStub::new();
+    stub.greet.returns("hi".to_owned());
+    let name = String::from("hello");
+    stub.greet(&name);
+    drop(name);
+    assert!(stub.greet.was_called_with_args(&"hello".to_owned()));
+  }
+
+  #[test]
+  fn records_slices_as_vecs_alongside_owned_args() {
+    let mut stub = TraitStub::new();
+    stub.write.returns_with(|args| args.0.len());
+    assert!(stub.write(b"abc", true) == 3);
+    assert!(stub.write.was_called_with_args(&(b"abc".to_vec(), true)));
+  }
+
+  #[test]
+  fn records_clones_of_borrowed_values() {
+    let mut stub = TraitStub::new();
+    let config = Config { name: "prod".to_owned(), retries: 3 };
+    stub.apply.when(|config| config.retries > 0, true);
+    stub.apply.returns(false);
+    assert!(stub.apply(&config));
+    assert!(stub.apply.get_args_for_call(0) == Some(config));
+  }
+
+  #[test]
+  fn records_user_conversions() {
+    let mut stub = TraitStub::new();
+    stub.rename.returns(true);
+    stub.rename(&Config { name: "prod".to_owned(), retries: 3 }, "staging");
+    assert!(stub.rename.was_called_with_args(&("prod".to_owned(), "staging".to_owned())));
+  }
+
+  #[test]
+  fn records_borrowed_args_of_static_methods() {
+    let parse = static_stub::<TraitStub, ArgWatchingStub<u32, String>>("parse");
+    parse.borrow_mut().returns(7);
+    assert!(TraitStub::parse("7") == 7);
+    assert!(parse.borrow().was_called_with_args(&"7".to_owned()));
+  }
+}
+
+mod opaque_args {
+  use rust_stub::*;
+  use std::sync::Arc;
+  use std::thread;
+
+  #[derive(Debug)]
+  struct Sink {
+    path: String,
+    bytes: Vec<u8>,
+  }
+
+  trait Trait {
+    fn submit(&self, _: Box<dyn Fn() -> u32>) -> bool;
+    fn flush(&self, _: Sink, _: bool) -> usize;
+  }
+
+  trait SyncTrait: Send + Sync {
+    fn flush(&self, _: Sink) -> usize;
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: submit (&self, job: Box<dyn Fn() -> u32>) -> bool}
+      {ArgWatchingStub: flush (&self, sink: Sink, sync: bool) -> usize}
+    }
+  }
+
+  struct SyncTraitStub {
+    flush: SyncArgWatchingStub<usize, Sink>,
+  }
+
+  instrument_stub! {
+    SyncTraitStub as SyncTrait {
+      {ArgWatchingStub: flush (&self, sink: Sink) -> usize}
+    }
+  }
+
+  fn sink(path: &str) -> Sink {
+    Sink { path: path.to_owned(), bytes: vec![1, 2] }
+  }
+
+  #[test]
+  fn lends_out_args_of_a_single_call() {
+    let mut stub = TraitStub::new();
+    stub.submit.returns(true);
+    stub.submit(Box::new(|| 7));
+    assert!(stub.submit.with_args_for_call(0, |job| job()) == Some(7));
+    assert!(stub.submit.with_args_for_call(1, |job| job()).is_none());
+  }
+
+  #[test]
+  fn lends_out_args_of_every_call() {
+    let mut stub = TraitStub::new();
+    stub.flush.returns(0);
+    stub.flush(sink("a"), true);
+    stub.flush(sink("b"), false);
+    let paths = stub.flush.recorded_args().iter().map(|args| args.0.path.clone()).collect::<Vec<_>>();
+    assert!(paths == vec!["a".to_owned(), "b".to_owned()]);
+  }
+
+  #[test]
+  fn keeps_debug_renderings() {
+    let mut stub = TraitStub::new();
+    stub.flush.returns(0);
+    stub.flush(sink("before"), true);
+    stub.flush.record_debug();
+    stub.flush(sink("after"), false);
+    assert!(stub.flush.key_for_call(0).is_none());
+    assert!(stub.flush.key_for_call(1) == Some("(Sink { path: \"after\", bytes: [1, 2] }, false)".to_owned()));
+    assert!(stub.flush.was_called_with_key("(Sink { path: \"after\", bytes: [1, 2] }, false)"));
+  }
+
+  #[test]
+  fn keeps_projected_keys() {
+    let mut stub = TraitStub::new();
+    stub.flush.returns(0);
+    stub.flush.record_keys(|args| format!("{}:{}", args.0.path, args.0.bytes.len()));
+    stub.flush(sink("log"), true);
+    assert!(stub.flush.was_called_with_key("log:2"));
+    assert!(!stub.flush.was_called_with_key("log:3"));
+  }
+
+  #[test]
+  fn keeps_keys_on_sync_stubs() {
+    let mut stub = SyncTraitStub { flush: SyncArgWatchingStub::new() };
+    stub.flush.returns(2);
+    stub.flush.record_keys(|sink| sink.path.clone());
+    let stub = Arc::new(stub);
+    let remote = stub.clone();
+    thread::spawn(move || remote.flush(sink("remote"))).join().unwrap();
+    assert!(stub.flush.was_called_with_key("remote"));
+    assert!(stub.flush.with_args_for_call(0, |sink| sink.bytes.len()) == Some(2));
+    assert!(stub.flush.recorded_args().len() == 1);
+  }
+}
+
+mod assertion_messages {
+  use rust_stub::*;
+  use rust_stub::matchers::*;
+
+  #[derive(Debug, PartialEq)]
+  struct Request {
+    path: String,
+    retries: u32,
+    verbose: bool,
+  }
+
+  trait Trait {
+    fn greet(&self, _: String, _: u32) -> String;
+    fn send(&self, _: Request) -> bool;
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: greet (&self, name: String, times: u32) -> String}
+      {ArgWatchingStub: send (&self, request: Request) -> bool}
+    }
+  }
+
+  fn request(path: &str, retries: u32) -> Request {
+    Request { path: path.to_owned(), retries, verbose: false }
+  }
+
+  #[test]
+  fn passes_when_the_args_were_seen() {
+    let mut stub = TraitStub::new();
+    stub.greet.returns("hi".to_owned());
+    stub.greet("bob".to_owned(), 3);
+    assert_called_with!(stub.greet, ("bob".to_owned(), 3));
+    assert_called_times!(stub.greet, 1);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [greet] to be called with (\"bob\", 3), but it was never called")]
+  fn reports_calls_that_never_happened() {
+    let stub = TraitStub::new();
+    assert_called_with!(stub.greet, ("bob".to_owned(), 3));
+  }
+
+  #[test]
+  #[should_panic(expected = "none of its 2 calls matched.\nCalls were:\n  #0 (\"alice\", 3)\n  #1 (\"bob\", 4)")]
+  fn lists_every_call() {
+    let mut stub = TraitStub::new();
+    stub.greet.returns("hi".to_owned());
+    stub.greet("alice".to_owned(), 3);
+    stub.greet("bob".to_owned(), 4);
+    assert_called_with!(stub.greet, ("bob".to_owned(), 3));
+  }
+
+  #[test]
+  #[should_panic(expected = "Closest match is call #1 (- expected, + actual):
+    Request {
+        path: \"/users\",
+  -     retries: 3,
+  +     retries: 1,
+        verbose: false,
+    }")]
+  fn diffs_the_closest_call_field_by_field() {
+    let mut stub = TraitStub::new();
+    stub.send.returns(true);
+    stub.send(request("/health", 0));
+    stub.send(request("/users", 1));
+    stub.send.verify_called_with(&request("/users", 3));
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [send] to be called with matching args, but none of its 1 calls matched.")]
+  fn reports_unmatched_matchers() {
+    let mut stub = TraitStub::new();
+    stub.send.returns(true);
+    stub.send(request("/health", 0));
+    stub.send.verify_called_matching(predicate(|request: &Request| request.retries > 0));
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [greet] to be called exactly 2 times, but it was called 1 times")]
+  fn reports_call_counts() {
+    let mut stub = TraitStub::new();
+    stub.greet.returns("hi".to_owned());
+    stub.greet("bob".to_owned(), 3);
+    assert_called_times!(stub.greet, 2);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [send] to be called exactly 1 times, but it was called 0 times")]
+  fn names_declared_stubs_before_their_first_call() {
+    let stub = TraitStub::new();
+    stub.send.verify_called_times(1);
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [unnamed stub] to be called exactly 1 times, but it was called 0 times")]
+  fn reports_stubs_that_were_never_named() {
+    let stub: ArgWatchingStub<bool, Request> = ArgWatchingStub::new();
+    stub.verify_called_times(1);
+  }
+}
+
+mod spy_stubs {
+  use rust_stub::*;
+
+  trait Store {
+    fn get(&self, key: &str) -> Option<u32>;
+    fn put(&mut self, key: String, val: u32);
+    fn len(&self) -> usize;
+    fn describe(&self) -> String;
+    fn open(&self) -> Handle;
+    fn into_keys(self) -> Vec<String>;
+  }
+
+  struct Handle;
+
+  #[derive(Default)]
+  struct MapStore {
+    entries: Vec<(String, u32)>,
+  }
+
+  impl Store for MapStore {
+    fn get(&self, key: &str) -> Option<u32> {
+      self.entries.iter().find(|entry| entry.0 == key).map(|entry| entry.1)
+    }
+    fn put(&mut self, key: String, val: u32) {
+      self.entries.push((key, val));
+    }
+    fn len(&self) -> usize {
+      self.entries.len()
+    }
+    fn describe(&self) -> String {
+      format!("{} entries", self.entries.len())
+    }
+    fn open(&self) -> Handle {
+      Handle
+    }
+    fn into_keys(self) -> Vec<String> {
+      self.entries.into_iter().map(|entry| entry.0).collect()
+    }
+  }
+
+  struct StoreSpy {
+    inner: MapStore,
+    get: ArgWatchingStub<Option<u32>, String>,
+    put: ArgWatchingStub<(), (String, u32)>,
+    len: SimpleStub<usize>,
+    open: ArgWatchingStub<Handle, ()>,
+    into_keys: SimpleStub<Vec<String>>,
+  }
+
+  impl StoreSpy {
+    fn new() -> StoreSpy {
+      StoreSpy {
+        inner: MapStore::default(),
+        get: ArgWatchingStub::new(),
+        put: ArgWatchingStub::new(),
+        len: SimpleStub::new(),
+        open: ArgWatchingStub::new(),
+        into_keys: SimpleStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    StoreSpy as Store spying inner {
+      {ArgWatchingStub: get (&self, key: &str => String) -> Option<u32>}
+      {ArgWatchingStub: put (&mut self, key: String, val: u32) -> ()}
+      {SimpleStub: len (&self) -> usize}
+      {nostub: describe (&self) -> String}
+      {ArgWatchingStub: open (&self) -> Handle}
+      {SimpleStub: into_keys (self) -> Vec<String>}
+    }
+  }
+
+  #[test]
+  fn forwards_calls_to_the_inner_implementation() {
+    let mut spy = StoreSpy::new();
+    spy.put("a".to_owned(), 1);
+    spy.put("b".to_owned(), 2);
+    assert!(spy.get("b") == Some(2));
+    assert!(spy.get("c").is_none());
+    assert!(spy.len() == 2);
+    assert!(spy.describe() == "2 entries");
+  }
+
+  #[test]
+  fn records_args_and_returns_of_forwarded_calls() {
+    let mut spy = StoreSpy::new();
+    spy.get.record_returns();
+    spy.put("a".to_owned(), 1);
+    assert!(spy.get("a") == Some(1));
+    assert!(spy.get("z").is_none());
+
+    assert!(spy.put.was_called_with_args(&("a".to_owned(), 1)));
+    assert!(spy.get.get_args_for_call(1) == Some("z".to_owned()));
+    assert!(*spy.get.recorded_returns() == [Some(1), None]);
+    assert!(spy.get.return_for_call(0) == Some(Some(1)));
+    assert!(spy.get.was_called_n_times(2));
+    assert!(spy.len.was_called_n_times(0));
+  }
+
+  #[test]
+  fn forwards_even_when_returns_were_configured() {
+    let mut spy = StoreSpy::new();
+    spy.len.returns(10);
+    assert!(spy.len() == 0);
+    assert!(spy.len.was_called_once());
+  }
+
+  #[test]
+  fn records_returns_only_once_asked_to() {
+    let mut spy = StoreSpy::new();
+    spy.put("a".to_owned(), 1);
+    assert!(spy.len() == 1);
+    assert!(spy.len.recorded_returns().is_empty());
+    spy.len.record_returns();
+    assert!(spy.len() == 1);
+    assert!(*spy.len.recorded_returns() == [1]);
+  }
+
+  #[test]
+  fn skips_recording_returns_that_are_not_clone() {
+    let spy = StoreSpy::new();
+    let _handle = spy.open();
+    assert!(spy.open.was_called_once());
+    assert!(spy.open.recorded_returns().is_empty());
+  }
+
+  #[test]
+  fn forwards_self_consuming_calls() {
+    let mut spy = StoreSpy::new();
+    spy.put("k".to_owned(), 3);
+    let into_keys = spy.into_keys.handle();
+    assert!(spy.into_keys() == vec!["k".to_owned()]);
+    assert!(into_keys.was_called_once());
+  }
+}
+
+mod partial_stubs {
+  use rust_stub::*;
+
+  trait Clock {
+    fn now(&self) -> u64;
+    fn zone(&self) -> String;
+    fn advance(&mut self, secs: u64);
+    fn stop(self) -> u64;
+    fn epoch() -> u64;
+    fn is_utc(&self) -> bool {
+      self.zone() == "UTC"
+    }
+  }
+
+  struct FixedClock {
+    secs: u64,
+  }
+
+  impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+      self.secs
+    }
+    fn zone(&self) -> String {
+      "UTC".to_owned()
+    }
+    fn advance(&mut self, secs: u64) {
+      self.secs += secs;
+    }
+    fn stop(self) -> u64 {
+      self.secs
+    }
+    fn epoch() -> u64 {
+      0
+    }
+  }
+
+  create_stub! {
+    ClockStub as Clock delegating real: FixedClock {
+      {SimpleStub: zone (&self) -> String}
+      {nostub: now (&self) -> u64}
+      {nostub: advance (&mut self, secs: u64) -> ()}
+      {nostub: stop (self) -> u64}
+      {nostub: epoch () -> u64}
+    }
+  }
+
+  #[test]
+  fn forwards_unstubbed_methods_to_the_fallback() {
+    let mut stub = ClockStub::new(FixedClock { secs: 100 });
+    stub.advance(5);
+    assert!(stub.now() == 105);
+    assert!(stub.real.secs == 105);
+    assert!(stub.stop() == 105);
+  }
+
+  #[test]
+  fn answers_stubbed_methods_from_the_stub() {
+    let mut stub = ClockStub::new(FixedClock { secs: 0 });
+    stub.zone.returns("CET".to_owned());
+    assert!(stub.zone() == "CET");
+    assert!(stub.zone.was_called_once());
+  }
+
+  #[test]
+  fn keeps_default_bodies_of_unlisted_methods() {
+    let mut stub = ClockStub::new(FixedClock { secs: 0 });
+    stub.zone.returns("UTC".to_owned());
+    assert!(stub.is_utc());
+    assert!(stub.zone.was_called_once());
+  }
+
+  #[test]
+  #[should_panic(expected = "Method [epoch] was not stubbed")]
+  fn panics_on_static_nostub_methods() {
+    ClockStub::epoch();
+  }
+
+  struct ManualStub {
+    fallback: FixedClock,
+    now: SimpleStub<u64>,
+  }
+
+  instrument_stub! {
+    ManualStub as Clock delegating fallback {
+      {SimpleStub: now (&self) -> u64}
+      {nostub: zone (&self) -> String}
+      {nostub: advance (&mut self, secs: u64) -> ()}
+      {nostub: stop (mut self) -> u64}
+      {nostub: epoch () -> u64}
+    }
+  }
+
+  #[test]
+  fn delegates_from_a_hand_written_struct() {
+    let mut stub = ManualStub { fallback: FixedClock { secs: 7 }, now: SimpleStub::new() };
+    stub.now.returns(1);
+    stub.advance(3);
+    assert!(stub.now() == 1);
+    assert!(stub.zone() == "UTC");
+    assert!(stub.stop() == 10);
+  }
+}
+
+mod stateful_interceptors {
+  use rust_stub::*;
+  use std::panic::{self, AssertUnwindSafe};
+  use std::rc::{Rc, Weak};
+  use std::sync::{mpsc, Arc};
+  use std::thread;
+
+  trait Log {
+    fn write(&self, line: &str) -> usize;
+    fn flush(&mut self, lines: u32);
+  }
+
+  create_stub! {
+    LogStub as Log {
+      {InterceptingStub: write (&self, line: &str) -> usize}
+      {InterceptingStub: flush (&mut self, lines: u32) -> ()}
+    }
+  }
+
+  #[test]
+  fn keeps_state_between_calls_from_shared_methods() {
+    let mut stub = LogStub::new();
+    let (tx, rx) = mpsc::channel();
+    let mut written = Vec::new();
+    stub.write.returns(0);
+    stub.write.set_interceptor(Box::new(move |line| {
+      written.push(line.to_owned());
+      tx.send(written.join(",")).unwrap();
+    }));
+    stub.write("a");
+    stub.write("b");
+    assert!(rx.try_iter().collect::<Vec<_>>() == vec!["a".to_owned(), "a,b".to_owned()]);
+  }
+
+  #[test]
+  fn keeps_state_between_calls_from_mut_methods() {
+    let mut stub = LogStub::new();
+    let (tx, rx) = mpsc::channel();
+    let mut total = 0;
+    stub.flush.set_interceptor(Box::new(move |lines| {
+      total += lines;
+      tx.send(total).unwrap();
+    }));
+    stub.flush(2);
+    stub.flush(3);
+    assert!(rx.try_iter().collect::<Vec<_>>() == vec![2, 5]);
+    assert!(stub.flush.was_called_n_times(2));
+  }
+
+  #[test]
+  fn answers_calls_made_from_inside_the_interceptor() {
+    let stub = Rc::new_cyclic(|this: &Weak<LogStub>| {
+      let mut stub = LogStub::new();
+      let this = this.clone();
+      stub.write.returns(1);
+      stub.write.set_interceptor(Box::new(move |line| {
+        if let Some(stub) = this.upgrade() {
+          assert!(stub.write(&format!("echo {}", line)) == 1);
+        }
+      }));
+      stub
+    });
+    assert!(stub.write("a") == 1);
+    assert!(stub.write.was_called_n_times(2));
+    assert!(stub.write("b") == 1);
+    assert!(stub.write.was_called_n_times(4));
+  }
+
+  #[test]
+  fn keeps_the_interceptor_after_it_panics() {
+    let mut stub = LogStub::new();
+    stub.write.set_interceptor(Box::new(|line| assert!(line != "boom")));
+    stub.write.returns(1);
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| stub.write("boom"))).is_err());
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| stub.write("boom"))).is_err());
+    assert!(stub.write("ok") == 1);
+  }
+
+  trait Counter: Send + Sync {
+    fn bump(&self, by: u32) -> u32;
+  }
+
+  struct CounterStub {
+    bump: SyncInterceptingStub<u32, dyn FnMut(u32) -> u32 + Send>,
+  }
+
+  instrument_stub! {
+    CounterStub as Counter {
+      {InterceptingStub: bump (&self, by: u32) -> u32}
+    }
+  }
+
+  #[test]
+  fn keeps_state_between_calls_from_several_threads() {
+    let mut stub = CounterStub { bump: SyncInterceptingStub::new() };
+    let mut total = 0;
+    stub.bump.set_interceptor(Box::new(move |by| {
+      total += by;
+      total
+    }));
+    let stub = Arc::new(stub);
+    let handles: Vec<_> = (0..4).map(|_| {
+      let stub = stub.clone();
+      thread::spawn(move || { stub.bump(1); })
+    }).collect();
+    for handle in handles {
+      handle.join().unwrap();
+    }
+    assert!(stub.bump(10) == 14);
+    assert!(stub.bump.was_called_n_times(5));
+  }
+
+  #[test]
+  fn skips_the_sync_interceptor_for_calls_made_from_inside_it() {
+    let stub = Arc::new_cyclic(|this: &::std::sync::Weak<CounterStub>| {
+      let mut stub = CounterStub { bump: SyncInterceptingStub::new() };
+      let this = this.clone();
+      stub.bump.returns(0);
+      stub.bump.set_interceptor(Box::new(move |by| {
+        this.upgrade().map_or(by, |stub| by + stub.bump(by))
+      }));
+      stub
+    });
+    assert!(stub.bump(3) == 3);
+    assert!(stub.bump.was_called_n_times(2));
+  }
+
+  #[test]
+  fn keeps_the_sync_interceptor_after_it_panics() {
+    let mut stub = CounterStub { bump: SyncInterceptingStub::new() };
+    stub.bump.set_interceptor(Box::new(|by| {
+      assert!(by != 0);
+      by * 2
+    }));
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| stub.bump(0))).is_err());
+    assert!(stub.bump(2) == 4);
+  }
+}
+
+mod faking_interceptors {
+  use rust_stub::*;
+
+  trait Pricing {
+    fn price(&self, sku: &str, qty: u32) -> u32;
+    fn discount(&mut self, sku: &str) -> Option<u32>;
+    fn rate(&self, sku: &str) -> u32;
+  }
+
+  struct PricingStub {
+    price: InterceptingStub<u32, dyn FnMut(&str, u32) -> u32>,
+    discount: InterceptingStub<Option<u32>, dyn FnMut(&str) -> Option<u32>>,
+    rate: InterceptingStub<u32, dyn FnMut(&str) -> Option<u32>>,
+  }
+
+  impl PricingStub {
+    fn new() -> PricingStub {
+      PricingStub {
+        price: InterceptingStub::new(),
+        discount: InterceptingStub::new(),
+        rate: InterceptingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    PricingStub as Pricing {
+      {InterceptingStub: price (&self, sku: &str, qty: u32) -> u32}
+      {InterceptingStub: discount (&mut self, sku: &str) -> Option<u32>}
+      {InterceptingStub: rate (&self, sku: &str) -> u32}
+    }
+  }
+
+  #[test]
+  fn returns_what_the_interceptor_returns() {
+    let mut stub = PricingStub::new();
+    stub.price.set_interceptor(Box::new(|sku, qty| if sku == "apple" { 3 * qty } else { 0 }));
+    assert!(stub.price("apple", 2) == 6);
+    assert!(stub.price("pear", 2) == 0);
+    assert!(stub.price.was_called_n_times(2));
+  }
+
+  #[test]
+  fn returns_options_from_interceptors_of_option_returning_methods() {
+    let mut stub = PricingStub::new();
+    stub.discount.returns(Some(50));
+    stub.discount.set_interceptor(Box::new(|sku| if sku == "apple" { Some(10) } else { None }));
+    assert!(stub.discount("apple") == Some(10));
+    assert!(stub.discount("pear").is_none());
+  }
+
+  #[test]
+  fn falls_through_to_the_configured_return_on_none() {
+    let mut stub = PricingStub::new();
+    stub.rate.returns(1);
+    stub.rate.set_interceptor(Box::new(|sku| if sku == "apple" { Some(7) } else { None }));
+    assert!(stub.rate("apple") == 7);
+    assert!(stub.rate("pear") == 1);
+    assert!(stub.rate.was_called_n_times(2));
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [rate] prior to invocation")]
+  fn panics_when_falling_through_with_no_configured_return() {
+    let mut stub = PricingStub::new();
+    stub.rate.set_interceptor(Box::new(|_| None));
+    stub.rate("pear");
+  }
+
+  #[test]
+  fn needs_no_configured_return_when_the_interceptor_answers() {
+    let mut stub = PricingStub::new();
+    let mut calls = 0;
+    stub.price.set_interceptor(Box::new(move |_, _| {
+      calls += 1;
+      calls
+    }));
+    assert!(stub.price("apple", 1) == 1);
+    assert!(stub.price("apple", 1) == 2);
+  }
+}
+
+mod out_params {
+  use rust_stub::*;
+
+  #[derive(Default)]
+  pub struct Config {
+    pub retries: u32,
+  }
+
+  trait Source {
+    fn read(&mut self, buf: &mut [u8]) -> usize;
+    fn fill(&self, out: &mut Vec<String>);
+    fn load(&self, config: &mut Config) -> bool;
+  }
+
+  create_stub! {
+    SourceStub as Source {
+      {InterceptingStub: read (&mut self, buf: &mut [u8]) -> usize}
+      {InterceptingStub: fill (&self, out: &mut Vec<String>) -> ()}
+      {InterceptingStub: load (&self, config: &mut Config) -> bool}
+    }
+  }
+
+  #[test]
+  fn copies_bytes_into_a_slice_per_call() {
+    let mut stub = SourceStub::new();
+    stub.read.returns_in_order(vec![3, 2, 0]);
+    stub.read.intercept_once(Box::new(|buf| buf[..3].copy_from_slice(b"abc")));
+    stub.read.intercept_once(Box::new(|buf| buf[..2].copy_from_slice(b"de")));
+
+    let mut buf = [0; 8];
+    let mut read = Vec::new();
+    loop {
+      let len = stub.read(&mut buf);
+      if len == 0 {
+        break;
+      }
+      read.extend_from_slice(&buf[..len]);
+    }
+    assert!(read == b"abcde");
+    assert!(stub.read.was_called_n_times(3));
+  }
+
+  #[test]
+  fn extends_a_vec() {
+    let mut stub = SourceStub::new();
+    stub.fill.set_interceptor(Box::new(|out| out.push("row".to_owned())));
+    let mut rows = vec!["header".to_owned()];
+    stub.fill(&mut rows);
+    stub.fill(&mut rows);
+    assert!(rows == vec!["header".to_owned(), "row".to_owned(), "row".to_owned()]);
+  }
+
+  #[test]
+  fn sets_a_field_before_returning() {
+    let mut stub = SourceStub::new();
+    stub.load.returns(true);
+    stub.load.intercept_once(Box::new(|config| config.retries = 5));
+    let mut config = Config::default();
+    assert!(stub.load(&mut config));
+    assert!(config.retries == 5);
+  }
+
+  #[test]
+  fn runs_queued_effects_ahead_of_the_interceptor() {
+    let mut stub = SourceStub::new();
+    stub.load.returns(true);
+    stub.load.set_interceptor(Box::new(|config| config.retries = 1));
+    stub.load.intercept_once(Box::new(|config| config.retries = 9));
+    let mut config = Config::default();
+    stub.load(&mut config);
+    assert!(config.retries == 9);
+    stub.load(&mut config);
+    assert!(config.retries == 1);
+  }
+}
+
+mod unified_stub {
+  use rust_stub::*;
+  use std::sync::mpsc;
+
+  trait Cache {
+    fn lookup(&self, key: &str, limit: u32) -> Option<u32>;
+    fn store(&mut self, key: String, val: u32) -> bool;
+    fn size() -> usize;
+  }
+
+  create_stub! {
+    CacheStub as Cache {
+      {Stub: lookup (&self, key: &str => String, limit: u32) -> Option<u32>}
+      {ArgWatchingStub: store (&mut self, key: String, val: u32) -> bool}
+      {Stub: size () -> usize}
+    }
+  }
+
+  #[test]
+  fn records_args_and_intercepts_the_same_method() {
+    let mut stub = CacheStub::new();
+    stub.lookup.returns(None);
+    stub.lookup.set_interceptor(Box::new(|key, limit| if key == "hit" { Some(Some(limit)) } else { None }));
+    assert!(stub.lookup("hit", 3) == Some(3));
+    assert!(stub.lookup("miss", 4).is_none());
+    assert!(stub.lookup.was_called_with_args(&("hit".to_owned(), 3)));
+    assert!(stub.lookup.get_args_for_call(1) == Some(("miss".to_owned(), 4)));
+  }
+
+  #[test]
+  fn combines_interceptors_with_return_rules() {
+    let mut stub = CacheStub::new();
+    let (tx, rx) = mpsc::channel();
+    stub.lookup.when(|args| args.1 == 0, Some(0));
+    stub.lookup.returns(Some(9));
+    stub.lookup.set_interceptor(Box::new(move |key, _| {
+      tx.send(key.to_owned()).unwrap();
+      None
+    }));
+    assert!(stub.lookup("a", 0) == Some(0));
+    assert!(stub.lookup("b", 1) == Some(9));
+    assert!(rx.try_iter().collect::<Vec<_>>() == vec!["a".to_owned(), "b".to_owned()]);
+  }
+
+  #[test]
+  fn records_returns_when_asked_to() {
+    let mut stub = CacheStub::new();
+    stub.store.returns_in_order(vec![true, false]);
+    stub.store.record_returns();
+    stub.store("a".to_owned(), 1);
+    stub.store("a".to_owned(), 2);
+    assert!(*stub.store.recorded_returns() == [true, false]);
+  }
+
+  #[test]
+  fn runs_argless_interceptors_on_arg_watching_stubs() {
+    let mut stub = CacheStub::new();
+    let (tx, rx) = mpsc::channel();
+    stub.store.returns(true);
+    stub.store.set_interceptor(Box::new(move || tx.send(()).unwrap()));
+    stub.store("a".to_owned(), 1);
+    assert!(rx.try_iter().count() == 1);
+  }
+
+  #[test]
+  fn stubs_static_methods() {
+    let size = static_stub::<CacheStub, Stub<(), usize, dyn FnMut() -> Option<usize>>>("size");
+    size.borrow_mut().returns(2);
+    assert!(CacheStub::size() == 2);
+    assert!(size.borrow().was_called_once());
+    reset_static_stubs::<CacheStub>();
+  }
+
+  #[test]
+  fn keeps_the_older_stub_names_as_aliases() {
+    let simple: SimpleStub<u32> = Stub::new();
+    let watching: ArgWatchingStub<u32, String> = Stub::new();
+    let intercepting: InterceptingStub<u32, dyn FnMut(u32)> = Stub::new();
+    assert!(!simple.was_called() && !watching.was_called() && !intercepting.was_called());
+  }
+}
+
+mod call_history {
+  use rust_stub::*;
+
+  trait Repo {
+    fn load(&self, id: u32) -> Option<String>;
+    fn save(&mut self, id: u32, val: String) -> bool;
+    fn flush(&self);
+  }
+
+  create_stub! {
+    RepoStub as Repo {
+      {ArgWatchingStub: load (&self, id: u32) -> Option<String>}
+      {ArgWatchingStub: save (&mut self, id: u32, val: String) -> bool}
+      {SimpleStub: flush (&self) -> ()}
+    }
+  }
+
+  #[test]
+  fn resets_calls_but_keeps_returns() {
+    let mut stub = RepoStub::new();
+    stub.load.returns(None);
+    stub.load(1);
+    stub.load.reset_calls();
+    assert!(!stub.load.was_called());
+    assert!(stub.load(2).is_none());
+    assert!(stub.load.get_args_for_call(0) == Some(2));
+  }
+
+  #[test]
+  fn resets_calls_seen_through_handles() {
+    let stub = RepoStub::new();
+    let flush = stub.flush.handle();
+    stub.flush();
+    stub.flush.reset_calls();
+    assert!(!flush.was_called());
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [load] prior to invocation")]
+  fn resets_returns_with_everything_else() {
+    let mut stub = RepoStub::new();
+    stub.load.returns(None);
+    stub.load.when_args(1, Some("one".to_owned()));
+    stub.load.reset_all();
+    stub.load(1);
+  }
+
+  #[test]
+  fn resets_interceptors_and_expectations() {
+    let mut stub = RepoStub::new();
+    stub.flush.expect_calls(3);
+    stub.flush.set_interceptor(Box::new(|| panic!("intercepted")));
+    stub.flush.reset_all();
+    stub.flush();
+    assert!(stub.flush.was_called_once());
+  }
+
+  #[test]
+  fn scopes_queries_to_calls_since_a_checkpoint() {
+    let mut stub = RepoStub::new();
+    stub.load.returns(None);
+    stub.load(1);
+    stub.load(2);
+    let setup = stub.load.checkpoint();
+    stub.load(3);
+    assert!(stub.load.since(setup).was_called_once());
+    assert!(stub.load.since(setup).was_called_with_args(&3));
+    assert!(stub.load.since(setup).never_called_with_args(&1));
+    assert!(stub.load.since(setup).recorded_args() == [3]);
+    assert!(stub.load.was_called_n_times(3));
+  }
+
+  #[test]
+  fn checkpoints_every_stub_in_a_struct() {
+    let mut stub = RepoStub::new();
+    stub.load.returns(None);
+    stub.save.returns(true);
+    stub.load(1);
+    stub.save(1, "a".to_owned());
+    let setup = stub.checkpoint();
+    stub.save(2, "b".to_owned());
+
+    assert!(!stub.load.since(setup).was_called());
+    assert!(stub.save.since(setup).get_args_for_call(0) == Some((2, "b".to_owned())));
+    assert!(!stub.flush.since(setup).was_called());
+  }
+
+  #[test]
+  fn resets_every_stub_in_a_struct() {
+    let mut stub = RepoStub::new();
+    stub.load.returns(None);
+    stub.save.returns(true);
+    stub.load(1);
+    stub.save(1, "a".to_owned());
+    stub.flush();
+    stub.reset_calls();
+    assert!(!stub.load.was_called() && !stub.save.was_called() && !stub.flush.was_called());
+
+    stub.reset_all();
+    stub.flush();
+    assert!(stub.flush.was_called_once());
+  }
+
+  #[test]
+  fn scopes_sync_stub_queries() {
+    let stub = SyncArgWatchingStub::<(), u32>::new();
+    stub.record_call("sync", 1);
+    let mark = stub.checkpoint();
+    stub.record_call("sync", 2);
+    assert!(stub.since(mark).was_called_with_args(&2));
+    assert!(stub.since(mark).was_called_once());
+    stub.reset_calls();
+    assert!(!stub.was_called());
+  }
+}
+
+mod call_counts {
+  use rust_stub::*;
+  use rust_stub::matchers::*;
+
+  trait Cache {
+    fn put(&self, key: u32, val: u32);
+    fn clear(&self);
+  }
+
+  create_stub! {
+    CacheStub as Cache {
+      {ArgWatchingStub: put (&self, key: u32, val: u32) -> ()}
+      {SimpleStub: clear (&self) -> ()}
+    }
+  }
+
+  #[test]
+  fn bounds_the_call_count() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    stub.put(1, 11);
+    stub.put(2, 20);
+    assert!(stub.put.was_called_at_least(3) && !stub.put.was_called_at_least(4));
+    assert!(stub.put.was_called_at_most(3) && !stub.put.was_called_at_most(2));
+    assert!(stub.put.was_called_between(2..=4) && !stub.put.was_called_between(0..=2));
+    assert!(stub.clear.was_never_called() && !stub.put.was_never_called());
+    stub.clear();
+    assert!(!stub.clear.was_never_called());
+  }
+
+  #[test]
+  fn checks_counts_with_structured_errors() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    stub.put(2, 20);
+    assert!(stub.put.check_called(CallCount::AtMost(2)).is_ok());
+    assert!(stub.put.check_called((2..=5).into()).is_ok());
+
+    let err = stub.put.check_called(CallCount::AtLeast(3)).unwrap_err();
+    assert!(err == CallCountError { name: Some("put"), expected: CallCount::AtLeast(3), actual: 2, matching_args: false });
+    assert!(err.to_string() == "Expected [put] to be called at least 3 times, but it was called 2 times");
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [put] to be called between 3 and 4 times, but it was called 2 times")]
+  fn verifies_a_range_of_counts() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    stub.put(2, 20);
+    stub.put.verify_called(CallCount::Between(3, 4));
+  }
+
+  #[test]
+  #[should_panic(expected = "Expected [put] to be called at most 1 times, but it was called 2 times")]
+  fn panics_on_drop_when_at_most_is_unmet() {
+    let mut stub = CacheStub::new();
+    stub.put.expect_at_most(1);
+    stub.put(1, 10);
+    stub.put(2, 20);
+  }
+
+  #[test]
+  fn counts_only_calls_with_matching_args() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    stub.put(2, 20);
+    stub.put(1, 11);
+    assert!(stub.put.calls_matching((eq(1), any())).was_called_n_times(2));
+    assert!(stub.put.calls_matching((any(), gt(15))).was_called_once());
+    assert!(stub.put.calls_with_args(&(2, 20)).was_called_once());
+    assert!(stub.put.calls_with_args(&(3, 30)).was_never_called());
+  }
+
+  #[test]
+  fn reports_matching_arg_counts() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    stub.put(2, 20);
+    let err = stub.put.calls_matching((eq(1), any())).check_called(CallCount::Exactly(2)).unwrap_err();
+    assert!(err.matching_args && err.actual == 1);
+    assert!(err.to_string() == "Expected [put] to be called with matching args exactly 2 times, but 1 of its calls matched");
+  }
+
+  #[test]
+  fn counts_matching_args_since_a_checkpoint() {
+    let stub = CacheStub::new();
+    stub.put(1, 10);
+    let mark = stub.checkpoint();
+    stub.put(1, 11);
+    stub.put(2, 20);
+    assert!(stub.put.since(mark).calls_matching((eq(1), any())).was_called_once());
+    assert!(stub.put.since(mark).calls_with_args(&(2, 20)).was_called_once());
+  }
+
+  #[test]
+  fn counts_matching_args_on_sync_stubs() {
+    let stub = SyncArgWatchingStub::<(), (u32, u32)>::new();
+    stub.record_call("put", (1, 10));
+    stub.record_call("put", (1, 11));
+    assert!(stub.calls_matching((eq(1), any())).was_called_n_times(2));
+    assert!(stub.calls_with_args(&(1, 11)).was_called_at_most(1));
+  }
+}
+
+mod call_stats {
+  use rust_stub::*;
+
+  trait Cache {
+    fn get(&self, key: &str) -> Option<u32>;
+    fn scale(&self, factor: f64) -> f64;
+  }
+
+  create_stub! {
+    CacheStub as Cache {
+      {ArgWatchingStub: get (&self, key: &str => String) -> Option<u32>}
+      {ArgWatchingStub: scale (&self, factor: f64) -> f64}
+    }
+  }
+
+  #[test]
+  fn counts_calls_for_each_args() {
+    let mut stub = CacheStub::new();
+    stub.get.returns(None);
+    stub.get("a");
+    stub.get("b");
+    stub.get("a");
+    stub.get("a");
+    assert!(stub.get.call_count_for(&"a".to_owned()) == 3);
+    assert!(stub.get.call_count_for(&"b".to_owned()) == 1);
+    assert!(stub.get.call_count_for(&"c".to_owned()) == 0);
+  }
+
+  #[test]
+  fn lists_distinct_args_in_order_of_first_call() {
+    let mut stub = CacheStub::new();
+    stub.get.returns(None);
+    for key in &["b", "a", "b", "c", "a"] {
+      stub.get(key);
+    }
+    assert!(stub.get.distinct_args() == vec!["b".to_owned(), "a".to_owned(), "c".to_owned()]);
+  }
+
+  #[test]
+  fn builds_a_histogram_in_order_of_first_call() {
+    let mut stub = CacheStub::new();
+    stub.get.returns(None);
+    for key in &["b", "a", "b", "c", "b"] {
+      stub.get(key);
+    }
+    let histogram = stub.get.call_histogram();
+    assert!(histogram.entries == vec![("b".to_owned(), 3), ("a".to_owned(), 1), ("c".to_owned(), 1)]);
+    assert!(histogram.len() == 3);
+    assert!(histogram.count_for(&"b".to_owned()) == 3);
+    assert!(histogram.repeated() == vec![(&"b".to_owned(), 3)]);
+    assert!(histogram.iter().map(|(_, count)| count).sum::<u32>() == 5);
+  }
+
+  #[test]
+  fn groups_args_that_cannot_be_hashed() {
+    let mut stub = CacheStub::new();
+    stub.scale.returns(0.0);
+    for &factor in &[0.5, 2.0, 0.5] {
+      stub.scale(factor);
+    }
+    let histogram = stub.scale.call_histogram_by_eq();
+    assert!(histogram.entries == vec![(0.5, 2), (2.0, 1)]);
+    assert!(stub.scale.call_count_for(&0.5) == 2);
+    assert!(histogram.distinct_args() == vec![0.5, 2.0]);
+  }
+
+  #[test]
+  fn builds_histograms_since_a_checkpoint() {
+    let mut stub = CacheStub::new();
+    stub.get.returns(None);
+    stub.get("a");
+    stub.get("b");
+    let mark = stub.checkpoint();
+    stub.get("b");
+    stub.get("b");
+    assert!(stub.get.since(mark).call_histogram().entries == vec![("b".to_owned(), 2)]);
+    assert!(stub.get.since(mark).call_count_for(&"a".to_owned()) == 0);
+  }
+
+  #[test]
+  fn builds_histograms_of_sync_stub_calls() {
+    let stub = SyncArgWatchingStub::<(), u32>::new();
+    for &key in &[7, 3, 7] {
+      stub.record_call("get", key);
+    }
+    assert!(stub.call_histogram().entries == vec![(7, 2), (3, 1)]);
+    assert!(stub.call_histogram_by_eq() == stub.call_histogram());
+    assert!(stub.distinct_args() == vec![7, 3]);
+    assert!(stub.call_count_for(&7) == 2);
+  }
+
+  #[test]
+  fn builds_empty_histograms_before_any_call() {
+    let stub = CacheStub::new();
+    assert!(stub.get.call_histogram().is_empty());
+    assert!(stub.get.distinct_args().is_empty());
+  }
+}
+
+mod sequenced_returns {
+  use rust_stub::*;
+
+  trait Trait {
+    fn simple_stub(&self) -> Result<u32, String>;
+    fn arg_watching_stub(&self, _: u32) -> u32;
+    fn intercepting_stub(&mut self, _: &u32) -> u32;
+  }
+
+  struct TraitStub {
+    simple_stub: SimpleStub<Result<u32, String>>,
+    arg_watching_stub: ArgWatchingStub<u32, u32>,
+    intercepting_stub: InterceptingStub<u32, dyn Fn(&u32)>,
+  }
+
+  impl TraitStub {
+    fn new() -> TraitStub {
+      TraitStub {
+        simple_stub: SimpleStub::new(),
+        arg_watching_stub: ArgWatchingStub::new(),
+        intercepting_stub: InterceptingStub::new(),
+      }
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {SimpleStub: simple_stub (&self) -> Result<u32, String>}
+      {ArgWatchingStub: arg_watching_stub (&self, a: u32) -> u32}
+      {InterceptingStub: intercepting_stub (&mut self, a: &u32) -> u32}
+    }
+  }
+
+  #[test]
+  fn returns_values_in_order_then_repeats_the_last() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.returns_in_order(vec![Ok(1), Err("timeout".to_owned())]);
+    assert!(stub.simple_stub() == Ok(1));
+    assert!(stub.simple_stub() == Err("timeout".to_owned()));
+    assert!(stub.simple_stub() == Err("timeout".to_owned()));
+    assert!(stub.simple_stub.was_called_n_times(3));
+  }
+
+  #[test]
+  fn cycles_through_values_when_asked() {
+    let mut stub = TraitStub::new();
+    stub.arg_watching_stub.returns_in_order(vec![1, 2]);
+    stub.arg_watching_stub.when_exhausted(Exhausted::Cycle);
+    let results: Vec<u32> = (0..5).map(|i| stub.arg_watching_stub(i)).collect();
+    assert!(results == vec![1, 2, 1, 2, 1]);
+    assert!(stub.arg_watching_stub.was_called_n_times(5));
+  }
+
+  #[test]
+  #[should_panic(expected = "All 2 return values for [intercepting_stub] were used up")]
+  fn panics_when_exhausted_if_asked() {
+    let mut stub = TraitStub::new();
+    stub.intercepting_stub.returns_in_order(vec![1, 2]);
+    stub.intercepting_stub.when_exhausted(Exhausted::Panic);
+    assert!(stub.intercepting_stub(&0) == 1);
+    assert!(stub.intercepting_stub(&0) == 2);
+    let _ = stub.intercepting_stub(&0);
+  }
+
+  #[test]
+  fn restarts_the_sequence_when_values_are_replaced() {
+    let mut stub = TraitStub::new();
+    stub.arg_watching_stub.returns_in_order(vec![1, 2]);
+    assert!(stub.arg_watching_stub(0) == 1);
+    stub.arg_watching_stub.returns_in_order(vec![3, 4]);
+    assert!(stub.arg_watching_stub(0) == 3);
+    stub.arg_watching_stub.returns(5);
+    assert!(stub.arg_watching_stub(0) == 5);
+    assert!(stub.arg_watching_stub(0) == 5);
+  }
+
+  #[test]
+  #[should_panic(expected = "#returns was not called on [simple_stub] prior to invocation")]
+  fn an_empty_sequence_counts_as_not_stubbed() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.returns_in_order(Vec::new());
+    let _ = stub.simple_stub();
+  }
+}
+
+mod stub_struct_macro {
+  use rust_stub::*;
+
+  trait Trait {
+    fn simple_stub(&self, _: u32) -> u32;
+  }
+
+  create_stub! {
+    TraitStub {
+      {SimpleStub: simple_stub (u32) -> u32}
+    }
+  }
+
+  instrument_stub! {
+    TraitStub as Trait {
+      {SimpleStub: simple_stub (&self, b1: u32) -> u32}
+    }
+  }
+
+  #[test]
+  fn it_returns_the_simple_stub_value() {
+    let mut stub = TraitStub::new();
+    stub.simple_stub.returns(7);
+    assert!(stub.simple_stub(1) == 7);
+    assert!(stub.simple_stub.was_called_once());
+  }
+}
+
+mod stub_declaration_macro {
+  use rust_stub::*;
+
+  trait Trait {
+    fn arg_watching_stub(&self, _: u32, _: String) -> Result<u32, u32>;
+    fn simple_stub(&mut self, _: u32) -> u32;
+    fn intercepting_stub(&self, _: &u32) -> u32;
+    fn consuming_stub(self) -> u32;
+    fn static_stub() -> u32;
+    fn no_stub(&self) -> u32;
+  }
+
+  create_stub! {
+    TraitStub as Trait {
+      {ArgWatchingStub: arg_watching_stub (&self, a: u32, b: String) -> Result<u32, u32>}
+      {SimpleStub: simple_stub (&mut self, a: u32) -> u32}
+      {InterceptingStub: intercepting_stub (&self, a: &u32) -> u32}
+      {SimpleStub: consuming_stub (self) -> u32}
+      {SimpleStub: static_stub () -> u32}
+      {nostub: no_stub (&self) -> u32}
+    }
+  }
+
+  #[test]
+  fn generates_the_struct_constructor_and_impl() {
+    let mut stub = TraitStub::new();
+    stub.arg_watching_stub.returns(Ok(1));
+    stub.simple_stub.returns(2);
+    stub.intercepting_stub.returns(3);
+    stub.intercepting_stub.set_interceptor(Box::new(|x| assert!(*x == 3)));
+    stub.consuming_stub.returns(4);
+    assert!(stub.arg_watching_stub(1, "a".to_owned()) == Ok(1));
+    assert!(stub.simple_stub(2) == 2);
+    assert!(stub.intercepting_stub(&3) == 3);
+    assert!(stub.arg_watching_stub.was_called_with_args(&(1, "a".to_owned())));
+    assert!(stub.simple_stub.was_called_once());
+    assert!(stub.intercepting_stub.was_called_once());
+    let consuming_stub = stub.consuming_stub.handle();
+    assert!(stub.consuming_stub() == 4);
+    assert!(consuming_stub.was_called_once());
+  }
+
+  #[test]
+  fn stubs_static_methods() {
+    static_stub::<TraitStub, SimpleStub<u32>>("static_stub").borrow_mut().returns(5);
+    assert!(TraitStub::static_stub() == 5);
+  }
+
+  #[test]
+  #[should_panic(expected = "Method [no_stub] was not stubbed")]
+  fn leaves_nostub_methods_unstubbed() {
+    let _ = TraitStub::new().no_stub();
+  }
+
+  #[test]
+  fn reports_every_stub_into_a_journal() {
+    let journal = CallJournal::new();
+    let mut stub = TraitStub::new();
+    stub.report_to(&journal);
+    stub.arg_watching_stub.returns(Ok(1));
+    stub.simple_stub.returns(2);
+    stub.intercepting_stub.returns(3);
+    let _ = stub.simple_stub(1);
+    let _ = stub.intercepting_stub(&1);
+    let _ = stub.arg_watching_stub(1, "a".to_owned());
+    journal.in_order(&["simple_stub", "intercepting_stub", "arg_watching_stub"]);
+  }
+}